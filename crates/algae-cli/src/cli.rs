@@ -1,14 +1,23 @@
+/// Implementation of the `completions` command.
+pub mod completions;
+
 /// Implementation of the `decrypt` command.
 pub mod decrypt;
 
 /// Implementation of the `encrypt` command.
 pub mod encrypt;
 
+/// Implementation of the `import` command.
+pub mod import;
+
 /// Implementation of the `keygen` command.
 pub mod keygen;
 
 /// Implementation of the `protect` command.
 pub mod protect;
 
+/// Implementation of the `reencrypt` command.
+pub mod reencrypt;
+
 /// Implementation of the `reveal` command.
 pub mod reveal;
@@ -0,0 +1,39 @@
+use std::io::Write;
+
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use miette::Result;
+
+/// Generate a shell completions script.
+///
+/// The script is printed to stdout. To install it, redirect it to wherever your
+/// shell loads completions from, for example:
+///
+/// ```console
+/// $ algae completions bash > ~/.local/share/bash-completion/completions/algae
+/// $ algae completions zsh > ~/.zfunc/_algae
+/// $ algae completions fish > ~/.config/fish/completions/algae.fish
+/// ```
+#[derive(Debug, Clone, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct CompletionsArgs {
+	/// Shell to generate a completions script for.
+	pub shell: Shell,
+}
+
+/// CLI command for the `completions` operation (shell completions generation).
+///
+/// This is generic over the top-level command, so it can be used from any CLI that embeds algae's.
+/// The `bin_name` is the name of the executable the completions are for.
+pub async fn run<C: CommandFactory>(
+	CompletionsArgs { shell }: CompletionsArgs,
+	bin_name: &str,
+) -> Result<()> {
+	generate::<C>(shell, bin_name, &mut std::io::stdout());
+	Ok(())
+}
+
+/// Write the completions script for a command and shell.
+pub fn generate<C: CommandFactory>(shell: Shell, bin_name: &str, out: &mut impl Write) {
+	clap_complete::generate(shell, &mut C::command(), bin_name, out);
+}
@@ -1,10 +1,14 @@
-use std::{fmt::Debug, path::PathBuf};
+use std::{
+	fmt::Debug,
+	path::{Path, PathBuf},
+};
 
 use clap::Parser;
-use miette::{miette, Result};
+use miette::{miette, IntoDiagnostic as _, Result, WrapErr as _};
+use tokio::fs::create_dir_all;
 
 use crate::{
-	files::{decrypt_file, remove_age_ext},
+	files::{decrypt_file, finish_output, in_dir, prepare_output, remove_age_ext},
 	keys::KeyArgs,
 };
 
@@ -25,6 +29,19 @@ pub struct DecryptArgs {
 	#[arg(short, long)]
 	pub output: Option<PathBuf>,
 
+	/// Directory to write the decrypted file into.
+	///
+	/// The file is named after the input file, with the `.age` removed. The
+	/// directory is created if it doesn't exist.
+	#[arg(long, conflicts_with = "output")]
+	pub output_dir: Option<PathBuf>,
+
+	/// Overwrite the output file if it already exists.
+	///
+	/// The existing file is only replaced once the new output has been completely written.
+	#[arg(long)]
+	pub force: bool,
+
 	#[command(flatten)]
 	#[allow(missing_docs, reason = "don't interfere with clap")]
 	pub key: KeyArgs,
@@ -35,17 +52,60 @@ pub async fn run(
 	DecryptArgs {
 		ref input,
 		output,
+		output_dir,
+		force,
 		key,
 	}: DecryptArgs,
 ) -> Result<()> {
 	let secret_key = key.require_secret_key().await?;
-	let output = if let Some(ref path) = output {
-		path.to_owned()
-	} else {
-		remove_age_ext(input)
-			.map_err(|_| miette!("Cannot guess output path, use --output to set one"))?
-	};
-
-	decrypt_file(input, output, secret_key).await?;
+	let output = output_path(input, output, output_dir.as_deref())?;
+	if let Some(dir) = output_dir {
+		create_dir_all(dir)
+			.await
+			.into_diagnostic()
+			.wrap_err("creating output directory")?;
+	}
+	let target = prepare_output(input, &output, force).await?;
+
+	let written = decrypt_file(input, &target, secret_key).await;
+	finish_output(written, &target, &output).await?;
 	Ok(())
 }
+
+fn output_path(
+	input: &Path,
+	output: Option<PathBuf>,
+	output_dir: Option<&Path>,
+) -> Result<PathBuf> {
+	if let Some(output) = output {
+		return Ok(output);
+	}
+
+	let output = remove_age_ext(input)
+		.map_err(|_| miette!("Cannot guess output path, use --output to set one"))?;
+	match output_dir {
+		Some(dir) => {
+			in_dir(dir, &output).ok_or_else(|| miette!("Cannot derive a filename from {input:?}"))
+		}
+		None => Ok(output),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn output_path_in_dir() {
+		let input = Path::new("encrypted/backup.tar.age");
+		assert_eq!(
+			output_path(input, None, None).unwrap(),
+			PathBuf::from("encrypted/backup.tar")
+		);
+		assert_eq!(
+			output_path(input, None, Some(Path::new("data"))).unwrap(),
+			Path::new("data").join("backup.tar")
+		);
+		assert!(output_path(Path::new("backup.tar"), None, Some(Path::new("data"))).is_err());
+	}
+}
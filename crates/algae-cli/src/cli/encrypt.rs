@@ -1,11 +1,14 @@
-use std::{fmt::Debug, path::PathBuf};
+use std::{
+	fmt::Debug,
+	path::{Path, PathBuf},
+};
 
 use clap::Parser;
-use miette::{IntoDiagnostic, Result, WrapErr};
-use tokio::fs::remove_file;
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
+use tokio::fs::{create_dir_all, remove_file};
 
 use crate::{
-	files::{append_age_ext, encrypt_file},
+	files::{append_age_ext, encrypt_file, finish_output, in_dir, prepare_output},
 	keys::KeyArgs,
 };
 
@@ -25,6 +28,19 @@ pub struct EncryptArgs {
 	#[arg(short, long)]
 	pub output: Option<PathBuf>,
 
+	/// Directory to write the encrypted file into.
+	///
+	/// The file is named after the input file, with `.age` appended. The
+	/// directory is created if it doesn't exist.
+	#[arg(long, conflicts_with = "output")]
+	pub output_dir: Option<PathBuf>,
+
+	/// Overwrite the output file if it already exists.
+	///
+	/// The existing file is only replaced once the new output has been completely written.
+	#[arg(long)]
+	pub force: bool,
+
 	/// Delete input file after encrypting.
 	#[arg(long = "rm")]
 	pub remove: bool,
@@ -39,14 +55,24 @@ pub async fn run(
 	EncryptArgs {
 		ref input,
 		output,
+		output_dir,
+		force,
 		key,
 		remove,
 	}: EncryptArgs,
 ) -> Result<()> {
 	let public_key = key.require_public_key().await?;
-	let output = output.unwrap_or_else(|| append_age_ext(input));
+	let output = output_path(input, output, output_dir.as_deref())?;
+	if let Some(dir) = output_dir {
+		create_dir_all(dir)
+			.await
+			.into_diagnostic()
+			.wrap_err("creating output directory")?;
+	}
+	let target = prepare_output(input, &output, force).await?;
 
-	encrypt_file(input, output, public_key).await?;
+	let written = encrypt_file(input, &target, public_key).await;
+	finish_output(written, &target, &output).await?;
 
 	if remove {
 		remove_file(input)
@@ -57,3 +83,38 @@ pub async fn run(
 
 	Ok(())
 }
+
+fn output_path(
+	input: &Path,
+	output: Option<PathBuf>,
+	output_dir: Option<&Path>,
+) -> Result<PathBuf> {
+	match (output, output_dir) {
+		(Some(output), _) => Ok(output),
+		(None, Some(dir)) => in_dir(dir, append_age_ext(input))
+			.ok_or_else(|| miette!("Cannot derive a filename from {input:?}")),
+		(None, None) => Ok(append_age_ext(input)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn output_path_in_dir() {
+		let input = Path::new("data/backup.tar");
+		assert_eq!(
+			output_path(input, None, None).unwrap(),
+			PathBuf::from("data/backup.tar.age")
+		);
+		assert_eq!(
+			output_path(input, None, Some(Path::new("encrypted"))).unwrap(),
+			Path::new("encrypted").join("backup.tar.age")
+		);
+		assert_eq!(
+			output_path(input, Some("elsewhere.age".into()), None).unwrap(),
+			PathBuf::from("elsewhere.age")
+		);
+	}
+}
@@ -0,0 +1,206 @@
+use std::path::PathBuf;
+
+use age::x25519;
+use clap::Parser;
+use miette::{bail, miette, Context as _, IntoDiagnostic as _, Result};
+use tokio::{
+	fs::{read_to_string, remove_file, File},
+	io::AsyncWriteExt,
+};
+
+use crate::{cli::keygen::public_key_file, passphrases::PassphraseArgs};
+
+/// Import an identity generated by `age-keygen`.
+///
+/// This takes a plaintext identity file as written by `age-keygen`:
+///
+/// ```key.txt
+/// # created: 2024-12-20T05:36:10+00:00
+/// # public key: age1c3jdepjm05aey2dq9dgkfn4utj9a776zwqzqcar3879smuh04ysqttvmyd
+/// AGE-SECRET-KEY-1N84CR29PJTUQA22ALHP4YDL5ZFMXPW5GVETVY3UK58ZD6NPNPDLS4MCZFS
+/// ```
+///
+/// and writes it as a passphrase-protected identity file and a public key file,
+/// the same as `keygen` does for new identities. If the `# public key` comment is
+/// present, it is checked against the public key derived from the secret key.
+///
+/// Plaintext identity files can also be used directly with `--key-path`, but
+/// it's best to keep them protected by a passphrase at rest.
+#[derive(Debug, Clone, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct ImportArgs {
+	/// Plaintext identity file to import.
+	pub input: PathBuf,
+
+	/// Path to write the protected identity file to.
+	#[arg(short, long, default_value = "identity.txt.age")]
+	pub output: PathBuf,
+
+	/// Path to write the public key file to.
+	///
+	/// Set to a single hyphen (`-`) to disable writing this file; the public key
+	/// will be printed to stdout in any case.
+	#[arg(long = "public", default_value = "identity.pub")]
+	pub public_path: PathBuf,
+
+	/// Delete the plaintext identity file after importing.
+	#[arg(long = "rm")]
+	pub remove: bool,
+
+	#[command(flatten)]
+	#[allow(missing_docs, reason = "don't interfere with clap")]
+	pub key: PassphraseArgs,
+}
+
+/// CLI command for the `import` operation (protecting an age-keygen identity).
+pub async fn run(
+	ImportArgs {
+		input,
+		output,
+		public_path,
+		remove,
+		key,
+	}: ImportArgs,
+) -> Result<()> {
+	let identity = read_to_string(&input)
+		.await
+		.into_diagnostic()
+		.wrap_err("reading identity file")?;
+	let public = parse_identity(&identity)?.to_public();
+
+	let key = key.require_with_confirmation().await?;
+	let protected = age::encrypt(&key, identity.as_bytes()).into_diagnostic()?;
+
+	File::create_new(&output)
+		.await
+		.into_diagnostic()
+		.wrap_err("opening the identity file")?
+		.write_all(&protected)
+		.await
+		.into_diagnostic()
+		.wrap_err("writing the identity")?;
+
+	println!("public key: {public}");
+	if public_path.to_string_lossy() != "-" {
+		File::create_new(&public_path)
+			.await
+			.into_diagnostic()
+			.wrap_err("opening the public key file")?
+			.write_all(public_key_file(&public, None).as_bytes())
+			.await
+			.into_diagnostic()
+			.wrap_err("writing the public key")?;
+	}
+
+	if remove {
+		remove_file(&input)
+			.await
+			.into_diagnostic()
+			.wrap_err("deleting input file")?;
+	}
+
+	Ok(())
+}
+
+/// Parse an age-keygen identity file, checking its public key comment if present.
+fn parse_identity(content: &str) -> Result<x25519::Identity> {
+	let mut secrets = Vec::new();
+	let mut public_comment = None;
+	for line in content.lines().map(str::trim) {
+		if let Some(public) = line.strip_prefix("# public key:") {
+			public_comment = Some(public.trim());
+		} else if line.is_empty() || line.starts_with('#') {
+			continue;
+		} else if line.starts_with("AGE-SECRET-KEY-") {
+			secrets.push(line);
+		} else {
+			bail!(
+				help = "only X25519 identities (AGE-SECRET-KEY-...) are supported",
+				"this doesn't look like an age-keygen identity file"
+			);
+		}
+	}
+
+	let [secret] = secrets[..] else {
+		bail!(
+			"expected a single identity in the file, found {}",
+			secrets.len()
+		);
+	};
+	let identity: x25519::Identity = secret
+		.parse()
+		.map_err(|err| miette!("{err}").wrap_err("parsing secret key"))?;
+
+	let public = identity.to_public().to_string();
+	if let Some(comment) = public_comment.filter(|comment| *comment != public) {
+		bail!("the public key in the file ({comment}) doesn't match the secret key's ({public})");
+	}
+
+	Ok(identity)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Read as _, Write as _};
+
+	use age::{secrecy::ExposeSecret as _, Identity};
+
+	use super::*;
+	use crate::keys::parse_id_as_identity;
+
+	fn keygen_file(id: &x25519::Identity, public: &str) -> String {
+		format!(
+			"# created: 2024-12-20T05:36:10+00:00\n# public key: {public}\n{}\n",
+			id.to_string().expose_secret()
+		)
+	}
+
+	#[test]
+	fn import_and_use() {
+		let id = x25519::Identity::generate();
+		let file = keygen_file(&id, &id.to_public().to_string());
+		let public = parse_identity(&file).unwrap().to_public();
+		assert_eq!(public.to_string(), id.to_public().to_string());
+
+		// what gets protected is the file as-is, which algae reads back like so
+		let identity = parse_id_as_identity(&file).unwrap();
+
+		let mut encrypted = Vec::new();
+		let mut writer = age::Encryptor::with_recipients(std::iter::once(&public as _))
+			.unwrap()
+			.wrap_output(&mut encrypted)
+			.unwrap();
+		writer.write_all(b"hello").unwrap();
+		writer.finish().unwrap();
+
+		let mut decrypted = Vec::new();
+		age::Decryptor::new(&encrypted[..])
+			.unwrap()
+			.decrypt(std::iter::once(&*identity as &dyn Identity))
+			.unwrap()
+			.read_to_end(&mut decrypted)
+			.unwrap();
+		assert_eq!(decrypted, b"hello");
+	}
+
+	#[test]
+	fn import_mismatched_public_key() {
+		let id = x25519::Identity::generate();
+		let other = x25519::Identity::generate().to_public().to_string();
+		let err = parse_identity(&keygen_file(&id, &other)).err().unwrap();
+		assert!(err.to_string().contains("doesn't match"), "{err}");
+	}
+
+	#[test]
+	fn import_rejects_other_files() {
+		let id = x25519::Identity::generate();
+		let two = format!(
+			"{}{}",
+			keygen_file(&id, &id.to_public().to_string()),
+			x25519::Identity::generate().to_string().expose_secret()
+		);
+		assert!(parse_identity(&two).is_err());
+		assert!(parse_identity(&id.to_public().to_string()).is_err());
+		assert!(parse_identity("# nothing here\n").is_err());
+	}
+}
@@ -30,6 +30,17 @@ use crate::passphrases::{Passphrase, PassphraseArgs};
 ///
 /// The public key is also printed to stdout.
 ///
+/// With `--comment`, a comment line is added above the public key, with the
+/// hostname and date, to tell keys apart when managing many of them:
+///
+/// ```identity.pub
+/// # backup server (gateway-01 2024-12-20)
+/// age1c3jdepjm05aey2dq9dgkfn4utj9a776zwqzqcar3879smuh04ysqttvmyd
+/// ```
+///
+/// Following age's convention, lines starting with `#` are ignored when reading
+/// the public key file.
+///
 /// By default this command prompts for a passphrase. This can be disabled with
 /// `--plaintext`; the default path `identity.txt` instead of `identity.txt.age`
 /// is used if `--output` isn't given, and the contents will be in plain text
@@ -58,6 +69,13 @@ pub struct KeygenArgs {
 	#[arg(long = "public", default_value = "identity.pub")]
 	pub public_path: PathBuf,
 
+	/// Comment to write above the public key in the public key file.
+	///
+	/// The hostname and current date are appended to it. It can't contain control
+	/// characters such as newlines, which would break the file.
+	#[arg(long, value_name = "TEXT", value_parser = parse_comment)]
+	pub comment: Option<String>,
+
 	/// INSECURE: write a plaintext identity.
 	#[arg(long)]
 	pub plaintext: bool,
@@ -74,28 +92,43 @@ pub struct KeygenArgs {
 	pub key: PassphraseArgs,
 }
 
+impl KeygenArgs {
+	/// The path the identity file is written to, with the default applied.
+	pub fn identity_path(&self) -> PathBuf {
+		self.output.clone().unwrap_or_else(|| {
+			if self.plaintext {
+				"identity.txt"
+			} else {
+				"identity.txt.age"
+			}
+			.into()
+		})
+	}
+}
+
 /// CLI command for the `keygen` operation (keypair generation).
-pub async fn run(
-	KeygenArgs {
-		output,
+pub async fn run(args: KeygenArgs) -> Result<()> {
+	generate(args).await.map(drop)
+}
+
+/// Generate and write out a keypair as the `keygen` command does, returning the public key.
+///
+/// This is useful to build on the `keygen` command, e.g. to additionally store the public key
+/// somewhere else.
+pub async fn generate(args: KeygenArgs) -> Result<x25519::Recipient> {
+	let output = args.identity_path();
+	let KeygenArgs {
 		public_path,
+		comment,
 		plaintext,
 		random_passphrase,
 		key,
-	}: KeygenArgs,
-) -> Result<()> {
+		..
+	} = args;
+
 	let secret = x25519::Identity::generate();
 	let public = secret.to_public();
 
-	let output = output.unwrap_or_else(|| {
-		if plaintext {
-			"identity.txt"
-		} else {
-			"identity.txt.age"
-		}
-		.into()
-	});
-
 	let identity = SecretString::from(format!(
 		"# created: {}\n# public key: {public}\n{}\n",
 		jiff::Timestamp::now(),
@@ -109,7 +142,7 @@ pub async fn run(
 			use rand::seq::SliceRandom;
 			let rng = &mut rand::thread_rng();
 			let words = diceware_wordlists::MINILOCK_WORDLIST.choose_multiple(rng, 6);
-			let phrase: String = Itertools::intersperse(words.map(|item| *item), "-").collect();
+			let phrase: String = Itertools::intersperse(words.copied(), "-").collect();
 			println!("passphrase: {phrase}");
 			Passphrase::new(phrase.into())
 		} else {
@@ -133,11 +166,63 @@ pub async fn run(
 			.await
 			.into_diagnostic()
 			.wrap_err("opening the public key file")?
-			.write_all(public.to_string().as_bytes())
+			.write_all(public_key_file(&public, comment.as_deref()).as_bytes())
 			.await
 			.into_diagnostic()
 			.wrap_err("writing the public key")?;
 	}
 
-	Ok(())
+	Ok(public)
+}
+
+fn parse_comment(comment: &str) -> std::result::Result<String, String> {
+	if comment.chars().any(char::is_control) {
+		Err("the comment can't contain control characters".into())
+	} else {
+		Ok(comment.into())
+	}
+}
+
+pub(crate) fn public_key_file(public: &x25519::Recipient, comment: Option<&str>) -> String {
+	let Some(comment) = comment else {
+		return public.to_string();
+	};
+
+	let date = jiff::Zoned::now().date();
+	match whoami::fallible::hostname() {
+		Ok(hostname) => format!("# {comment} ({hostname} {date})\n{public}\n"),
+		Err(_) => format!("# {comment} ({date})\n{public}\n"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn public_key_file_comment() {
+		let public = x25519::Identity::generate().to_public();
+		assert_eq!(public_key_file(&public, None), public.to_string());
+
+		let file = public_key_file(&public, Some("backup server"));
+		let mut lines = file.lines();
+		let comment = lines.next().unwrap();
+		assert!(comment.starts_with("# backup server ("), "{comment}");
+		assert!(comment.ends_with(&format!("{})", jiff::Zoned::now().date())));
+		assert_eq!(lines.next(), Some(public.to_string().as_str()));
+		assert_eq!(lines.next(), None);
+	}
+
+	#[test]
+	fn comment_without_control_characters() {
+		let parse = |comment: &str| {
+			KeygenArgs::try_parse_from(["keygen", "--comment", comment]).map(|args| args.comment)
+		};
+		assert_eq!(
+			parse("backup server").unwrap().as_deref(),
+			Some("backup server")
+		);
+		assert!(parse("backup\nage1fake").is_err());
+		assert!(parse("tab\there").is_err());
+	}
 }
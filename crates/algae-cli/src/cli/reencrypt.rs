@@ -0,0 +1,109 @@
+use std::{ffi::OsString, fmt::Debug, path::PathBuf};
+
+use age::Recipient;
+use clap::{ArgGroup, Parser};
+use miette::{bail, Context as _, IntoDiagnostic as _, Result};
+use tokio::fs::{read_to_string, remove_file, rename};
+
+use crate::{
+	files::reencrypt_file,
+	keys::{parse_id_as_recipient, KeyArgs},
+};
+
+/// Re-encrypt a file to a different public key.
+///
+/// This decrypts the file with a secret key or identity, and encrypts it again
+/// to a new recipient, without ever writing the plaintext to disk. This is useful
+/// to rotate which key can read an archive.
+///
+/// Either of `--key-path` or `--key` must be provided for the current secret key,
+/// and either of `--recipient-path` or `--recipient` for the new public key.
+///
+/// The original file is left intact, unless `--in-place` is given.
+#[derive(Debug, Clone, Parser)]
+#[command(group = ArgGroup::new("out").required(true))]
+#[command(group = ArgGroup::new("to").required(true))]
+pub struct ReencryptArgs {
+	/// File to be re-encrypted.
+	pub input: PathBuf,
+
+	/// Path or filename to write the re-encrypted file to.
+	#[arg(short, long, group = "out")]
+	pub output: Option<PathBuf>,
+
+	/// Replace the input file with the re-encrypted one.
+	///
+	/// The new file is written alongside the input first, and only replaces it
+	/// once re-encryption has completed successfully.
+	#[arg(long, group = "out")]
+	pub in_place: bool,
+
+	/// Path to the public key (or identity) file of the new recipient.
+	#[arg(long, group = "to")]
+	pub recipient_path: Option<PathBuf>,
+
+	/// The public key of the new recipient as a string.
+	///
+	/// ## Example
+	///
+	/// ```console
+	/// --recipient age1c3jdepjm05aey2dq9dgkfn4utj9a776zwqzqcar3879smuh04ysqttvmyd
+	/// ```
+	#[arg(short, long, group = "to", verbatim_doc_comment)]
+	pub recipient: Option<String>,
+
+	#[command(flatten)]
+	#[allow(missing_docs, reason = "don't interfere with clap")]
+	pub key: KeyArgs,
+}
+
+/// CLI command for the `reencrypt` operation (changing the public key of a file).
+pub async fn run(
+	ReencryptArgs {
+		ref input,
+		output,
+		in_place: _,
+		recipient_path,
+		recipient,
+		key,
+	}: ReencryptArgs,
+) -> Result<()> {
+	// clap requires one of these, so without a path there's a key
+	let recipient = match recipient_path {
+		Some(path) => read_to_string(&path)
+			.await
+			.into_diagnostic()
+			.wrap_err("reading recipient file")?,
+		None => recipient.unwrap_or_default(),
+	};
+	let recipient: Box<dyn Recipient + Send> = parse_id_as_recipient(&recipient)?;
+	let secret_key = key.require_secret_key().await?;
+
+	// clap requires one of --output or --in-place, so without an output it's in place: then
+	// write to a file alongside, and only replace the input at the end
+	let in_place = output.is_none();
+	let target = output.unwrap_or_else(|| {
+		let mut temporary = OsString::from(input.as_os_str());
+		temporary.push(".new");
+		PathBuf::from(temporary)
+	});
+	if target.exists() {
+		bail!("{target:?} already exists, not overwriting it");
+	}
+
+	if let Err(err) = reencrypt_file(input, &target, secret_key, recipient).await {
+		remove_file(&target).await.ok();
+		return Err(err);
+	}
+
+	if !in_place {
+		return Ok(());
+	}
+
+	rename(&target, input)
+		.await
+		.into_diagnostic()
+		.wrap_err("replacing the input file")?;
+
+	Ok(())
+}
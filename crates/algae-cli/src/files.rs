@@ -1,4 +1,5 @@
 use std::{
+	ffi::OsString,
 	fmt::Debug,
 	io::{stderr, IsTerminal as _},
 	path::{Path, PathBuf},
@@ -6,12 +7,15 @@ use std::{
 
 use age::{Identity, Recipient};
 use indicatif::{ProgressBar, ProgressBarIter, ProgressStyle};
-use miette::{Context as _, IntoDiagnostic as _, Result};
-use tokio::{fs::File, io::AsyncRead};
+use miette::{bail, Context as _, IntoDiagnostic as _, Result};
+use tokio::{
+	fs::{canonicalize, remove_file, rename, File},
+	io::AsyncRead,
+};
 use tokio_util::compat::{TokioAsyncReadCompatExt as _, TokioAsyncWriteCompatExt as _};
 use tracing::instrument;
 
-use crate::streams::{decrypt_stream, encrypt_stream};
+use crate::streams::{decrypt_stream, encrypt_stream, reencrypt_stream};
 
 /// Wraps a [`tokio::io::AsyncRead`] with an [`indicatif::ProgressBar`].
 ///
@@ -92,6 +96,41 @@ pub async fn decrypt_file(
 	decrypt_stream(with_progress_bar(input_length, input).compat(), output, key).await
 }
 
+/// Re-encrypt a path to another, from an [`Identity`] to a new [`Recipient`].
+///
+/// The plaintext is never written to disk. If stderr is a terminal, this will show a progress bar.
+#[instrument(level = "debug", skip(identity, recipient))]
+pub async fn reencrypt_file(
+	input_path: impl AsRef<Path> + Debug,
+	output_path: impl AsRef<Path> + Debug,
+	identity: Box<dyn Identity>,
+	recipient: Box<dyn Recipient + Send>,
+) -> Result<u64> {
+	let input = File::open(input_path)
+		.await
+		.into_diagnostic()
+		.wrap_err("opening the input file")?;
+	let input_length = input
+		.metadata()
+		.await
+		.into_diagnostic()
+		.wrap_err("reading input file length")?
+		.len();
+
+	let output = File::create_new(output_path)
+		.await
+		.into_diagnostic()
+		.wrap_err("opening the re-encrypted output")?;
+
+	reencrypt_stream(
+		with_progress_bar(input_length, input).compat(),
+		output.compat_write(),
+		identity,
+		recipient,
+	)
+	.await
+}
+
 /// Append `.age` to a file path.
 pub fn append_age_ext(path: impl AsRef<Path>) -> PathBuf {
 	let mut path = path.as_ref().as_os_str().to_owned();
@@ -109,3 +148,130 @@ pub fn remove_age_ext<T: AsRef<Path>>(path: T) -> std::result::Result<PathBuf, T
 		Ok(path.as_ref().with_extension(""))
 	}
 }
+
+/// Place a file path within a directory, keeping only its base filename.
+///
+/// Returns `None` if the path has no filename (e.g. it ends in `..`).
+pub fn in_dir(dir: impl AsRef<Path>, path: impl AsRef<Path>) -> Option<PathBuf> {
+	path.as_ref()
+		.file_name()
+		.map(|name| dir.as_ref().join(name))
+}
+
+/// Check that an output path can be written to, and return the path to write to.
+///
+/// If a file already exists at the path, this errors, unless `force` is true, in which case the
+/// returned path is alongside the existing file, which is only replaced by [`finish_output()`]
+/// once the new output is complete. The output is never allowed to be the input.
+pub async fn prepare_output(
+	input: impl AsRef<Path>,
+	output: impl AsRef<Path>,
+	force: bool,
+) -> Result<PathBuf> {
+	let (input, output) = (input.as_ref(), output.as_ref());
+	if !output.exists() {
+		return Ok(output.into());
+	}
+
+	if canonicalize(input).await.ok() == canonicalize(output).await.ok() {
+		bail!("{output:?} is the input file, not overwriting it");
+	}
+
+	if !force {
+		bail!("{output:?} already exists, use --force to overwrite it");
+	}
+
+	let mut temporary = OsString::from(output.as_os_str());
+	temporary.push(".new");
+	let temporary = PathBuf::from(temporary);
+	if temporary.exists() {
+		bail!("{temporary:?} already exists, not overwriting it");
+	}
+
+	Ok(temporary)
+}
+
+/// Put the output written to the path from [`prepare_output()`] in place.
+///
+/// If writing failed, the partial output is deleted, and any existing file is left untouched.
+pub async fn finish_output(written: Result<u64>, target: &Path, output: &Path) -> Result<u64> {
+	let length = match written {
+		Ok(length) => length,
+		Err(err) => {
+			remove_file(target).await.ok();
+			return Err(err);
+		}
+	};
+
+	if target != output {
+		rename(target, output)
+			.await
+			.into_diagnostic()
+			.wrap_err("replacing the existing output file")?;
+	}
+
+	Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn in_dir_keeps_filename() {
+		assert_eq!(
+			in_dir("out", append_age_ext("some/where/backup.tar")),
+			Some(Path::new("out").join("backup.tar.age"))
+		);
+		assert_eq!(
+			in_dir("out", remove_age_ext("some/where/backup.tar.age").unwrap()),
+			Some(Path::new("out").join("backup.tar"))
+		);
+		assert_eq!(in_dir("out", "some/where/.."), None);
+	}
+
+	#[tokio::test]
+	async fn prepare_output_keeps_existing_files() {
+		let tempdir = tempfile::tempdir().unwrap();
+		let dir = tempdir.path();
+		let input = dir.join("backup.tar");
+		let output = dir.join("backup.tar.age");
+		tokio::fs::write(&input, "plaintext").await.unwrap();
+
+		assert_eq!(
+			prepare_output(&input, &output, false).await.unwrap(),
+			output
+		);
+
+		tokio::fs::write(&output, "existing").await.unwrap();
+		assert!(prepare_output(&input, &output, false).await.is_err());
+		assert!(prepare_output(&input, &input, true).await.is_err());
+		assert!(
+			prepare_output(&input, dir.join(".").join("backup.tar"), true)
+				.await
+				.is_err()
+		);
+
+		let target = prepare_output(&input, &output, true).await.unwrap();
+		assert_eq!(target, dir.join("backup.tar.age.new"));
+		tokio::fs::write(&target, "partial").await.unwrap();
+		assert!(
+			finish_output(Err(miette::miette!("failed")), &target, &output)
+				.await
+				.is_err()
+		);
+		assert!(!target.exists());
+		assert_eq!(
+			tokio::fs::read_to_string(&output).await.unwrap(),
+			"existing"
+		);
+
+		tokio::fs::write(&target, "complete").await.unwrap();
+		assert_eq!(finish_output(Ok(8), &target, &output).await.unwrap(), 8);
+		assert!(!target.exists());
+		assert_eq!(
+			tokio::fs::read_to_string(&output).await.unwrap(),
+			"complete"
+		);
+	}
+}
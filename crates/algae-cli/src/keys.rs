@@ -270,40 +270,138 @@ impl KeyArgs {
 	}
 }
 
-fn parse_id_as_identity(id: &str) -> Result<Box<dyn Identity>> {
-	if id.starts_with("AGE-SECRET-KEY") {
-		id.parse::<x25519::Identity>()
+/// Parse a secret key or identity file contents into an [`Identity`].
+///
+/// The input can be a single Bech32 secret key, or an identity file with exactly one identity.
+/// Passphrase-protected identity files must be decrypted first.
+pub fn parse_id_as_identity(id: &str) -> Result<Box<dyn Identity>> {
+	if let Some(key) = single_key(id).filter(|key| key.starts_with("AGE-SECRET-KEY")) {
+		key.parse::<x25519::Identity>()
 			.map(|sec| Box::new(sec) as _)
 			.map_err(|err| miette!("{err}").wrap_err("parsing secret key"))
 	} else {
-		IdentityFile::from_buffer(id.as_bytes())
+		let mut ids = IdentityFile::from_buffer(id.as_bytes())
 			.into_diagnostic()
 			.wrap_err("parsing identity")?
 			.into_identities()
 			.into_diagnostic()
-			.wrap_err("parsing keys from identity")?
-			.pop()
-			.ok_or_else(|| miette!("no identity available"))
+			.wrap_err("parsing keys from identity")?;
+		if ids.len() > 1 {
+			return Err(multiple_identities(ids.len()));
+		}
+
+		ids.pop().ok_or_else(|| miette!("no identity available"))
 	}
 }
 
-fn parse_id_as_recipient(id: &str) -> Result<Box<dyn Recipient + Send>> {
-	if id.starts_with("age") {
-		id.parse::<x25519::Recipient>()
+/// Parse a public key, secret key, or identity file contents into a [`Recipient`].
+///
+/// When given a secret key or identity, the corresponding public key is derived.
+pub fn parse_id_as_recipient(id: &str) -> Result<Box<dyn Recipient + Send>> {
+	if let Some(key) = single_key(id).filter(|key| key.starts_with("age")) {
+		key.parse::<x25519::Recipient>()
 			.map(|key| Box::new(key) as _)
 			.map_err(|err| miette!("{err}").wrap_err("parsing public key"))
-	} else if id.starts_with("AGE-SECRET-KEY") {
-		id.parse::<x25519::Identity>()
+	} else if let Some(key) = single_key(id).filter(|key| key.starts_with("AGE-SECRET-KEY")) {
+		key.parse::<x25519::Identity>()
 			.map(|sec| Box::new(sec.to_public()) as _)
 			.map_err(|err| miette!("{err}").wrap_err("parsing secret key"))
 	} else {
-		IdentityFile::from_buffer(id.as_bytes())
+		let mut recipients = IdentityFile::from_buffer(id.as_bytes())
 			.into_diagnostic()
 			.wrap_err("parsing identity")?
 			.to_recipients()
 			.into_diagnostic()
-			.wrap_err("parsing recipients from identity")?
+			.wrap_err("parsing recipients from identity")?;
+		if recipients.len() > 1 {
+			return Err(multiple_identities(recipients.len()));
+		}
+
+		recipients
 			.pop()
 			.ok_or_else(|| miette!("no recipient available in identity"))
 	}
 }
+
+/// Returns the key if the input contains exactly one, ignoring `#` comments and blank lines.
+fn single_key(id: &str) -> Option<&str> {
+	let mut keys = id
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'));
+	let key = keys.next()?;
+	keys.next().is_none().then_some(key)
+}
+
+fn multiple_identities(n: usize) -> miette::Report {
+	miette!(
+		help = "split the file so it contains a single identity, or use age directly instead",
+		"this identity file contains {n} identities, but algae only supports one identity per file"
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use age_core::secrecy::ExposeSecret as _;
+
+	use super::*;
+
+	fn two_identities() -> String {
+		format!(
+			"# first\n{}\n# second\n{}\n",
+			x25519::Identity::generate().to_string().expose_secret(),
+			x25519::Identity::generate().to_string().expose_secret(),
+		)
+	}
+
+	#[test]
+	fn single_secret_key() {
+		let id = x25519::Identity::generate();
+		let key = format!("{}\n", id.to_string().expose_secret());
+		assert!(parse_id_as_identity(&key).is_ok());
+		assert!(parse_id_as_recipient(&key).is_ok());
+	}
+
+	#[test]
+	fn single_identity_file() {
+		let id = x25519::Identity::generate();
+		let file = format!(
+			"# created: 2024-12-20T05:36:10.267871872+00:00\n# public key: {}\n{}\n",
+			id.to_public(),
+			id.to_string().expose_secret()
+		);
+		assert!(parse_id_as_identity(&file).is_ok());
+		assert!(parse_id_as_recipient(&file).is_ok());
+	}
+
+	#[test]
+	fn commented_public_key() {
+		let id = x25519::Identity::generate();
+		let file = format!("# backup server (gateway 2024-12-20)\n{}\n", id.to_public());
+		let recipient = parse_id_as_recipient(&file).unwrap();
+
+		let mut encrypted = Vec::new();
+		let mut writer = age::Encryptor::with_recipients(std::iter::once(&*recipient as _))
+			.unwrap()
+			.wrap_output(&mut encrypted)
+			.unwrap();
+		std::io::Write::write_all(&mut writer, b"hello").unwrap();
+		writer.finish().unwrap();
+		assert_eq!(age::decrypt(&id, &encrypted).unwrap(), b"hello");
+	}
+
+	#[test]
+	fn multi_identity_file() {
+		let file = two_identities();
+		let expected =
+			"this identity file contains 2 identities, but algae only supports one identity per file";
+		assert_eq!(
+			parse_id_as_identity(&file).err().unwrap().to_string(),
+			expected
+		);
+		assert_eq!(
+			parse_id_as_recipient(&file).err().unwrap().to_string(),
+			expected
+		);
+	}
+}
@@ -10,7 +10,7 @@
 //!
 //! Algae is a simplified profile of the excellent [age](https://age-encryption.org/v1) format.
 //!
-//! It implements five functions for the most common operations, and tries to be as obvious and
+//! It implements seven functions for the most common operations, and tries to be as obvious and
 //! hard-to-misuse as possible, without being prohibitively hard to use, and while retaining
 //! forward-compatibility with age (all algae products can be used with age, but not all age
 //! products may be used with algae).
@@ -29,13 +29,21 @@
 //! `algae protect identity.txt`. These commands are not special to identity files: you can
 //! `protect` (encrypt) and `reveal` (decrypt) arbitrary files with a passphrase.
 //!
+//! To change which key can read an encrypted file, use
+//! `algae reencrypt -k identity.txt.age --recipient-path new.pub filename.age --in-place`. The
+//! plaintext is never written to disk.
+//!
+//! To use an identity generated by `age-keygen`, protect it with a passphrase with
+//! `algae import key.txt`. This writes `identity.txt.age` and `identity.pub` as `keygen` does.
+//!
 //! # The profile
 //!
 //! - Keypair-based commands use [X25519](age::x25519).
 //! - Passphrase-based commands use [scrypt](age::scrypt).
 //! - Plugins are not supported.
-//! - Multiple recipients are not supported (age-produced multi-recipient files _may_ be decrypted).
-//! - Identity files with multiple identities are not supported (they _might_ work, but behaviour is unspecified).
+//! - Multiple recipients are not supported (age-produced multi-recipient files can be decrypted if
+//!   the given identity matches one of the recipients, and otherwise produce a specific error).
+//! - Identity files with multiple identities are not supported, and are rejected with an error.
 //! - Passphrase entry is done with `pinentry` when available, and falls back to a terminal prompt.
 //!
 //! # The library
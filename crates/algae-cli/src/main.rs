@@ -5,7 +5,7 @@ use clap::Parser;
 ///
 /// Algae is a simplified profile of the excellent [age](https://age-encryption.org/v1) format.
 ///
-/// It implements five functions for the most common operations, and tries to be as obvious and
+/// It implements seven functions for the most common operations, and tries to be as obvious and
 /// hard-to-misuse as possible, without being prohibitively hard to use, and while retaining
 /// forward-compatibility with age (all algae products can be used with age, but not all age
 /// products may be used with algae).
@@ -24,6 +24,13 @@ use clap::Parser;
 /// `algae protect identity.txt`. These commands are not special to identity files: you can
 /// `protect` (encrypt) and `reveal` (decrypt) arbitrary files with a passphrase.
 ///
+/// To change which key can read an encrypted file, use
+/// `algae reencrypt -k identity.txt.age --recipient-path new.pub filename.age --in-place`. The
+/// plaintext is never written to disk.
+///
+/// To use an identity generated by `age-keygen`, protect it with a passphrase with
+/// `algae import key.txt`. This writes `identity.txt.age` and `identity.pub` as `keygen` does.
+///
 /// Every command has a short help (`-h`), which is useful to recall the name of options, and a
 /// long help (`--help`), which contains more details and guide-level information.
 #[derive(Parser)]
@@ -34,10 +41,14 @@ use clap::Parser;
 	after_long_help = ""
 )]
 enum Command {
+	#[command(hide = true)]
+	Completions(completions::CompletionsArgs),
 	Decrypt(decrypt::DecryptArgs),
 	Encrypt(encrypt::EncryptArgs),
+	Import(import::ImportArgs),
 	Keygen(keygen::KeygenArgs),
 	Protect(protect::ProtectArgs),
+	Reencrypt(reencrypt::ReencryptArgs),
 	Reveal(reveal::RevealArgs),
 }
 
@@ -49,11 +60,42 @@ fn main() -> miette::Result<()> {
 		.block_on(async {
 			let command = Command::parse();
 			match command {
+				Command::Completions(args) => {
+					completions::run::<Command>(args, env!("CARGO_BIN_NAME")).await
+				}
 				Command::Decrypt(args) => decrypt::run(args).await,
 				Command::Encrypt(args) => encrypt::run(args).await,
+				Command::Import(args) => import::run(args).await,
 				Command::Keygen(args) => keygen::run(args).await,
 				Command::Protect(args) => protect::run(args).await,
+				Command::Reencrypt(args) => reencrypt::run(args).await,
 				Command::Reveal(args) => reveal::run(args).await,
 			}
 		})
 }
+
+#[cfg(test)]
+mod tests {
+	use clap_complete::Shell;
+
+	use super::*;
+
+	#[test]
+	fn bash_completions() {
+		let mut script = Vec::new();
+		completions::generate::<Command>(Shell::Bash, "algae", &mut script);
+		let script = String::from_utf8(script).unwrap();
+		assert!(script.contains("complete -F _algae"));
+		for subcommand in [
+			"decrypt",
+			"encrypt",
+			"import",
+			"keygen",
+			"protect",
+			"reencrypt",
+			"reveal",
+		] {
+			assert!(script.contains(subcommand), "missing {subcommand}");
+		}
+	}
+}
@@ -1,7 +1,11 @@
 use std::iter;
 
-use age::{Decryptor, Encryptor, Identity, Recipient};
-use miette::{Context as _, IntoDiagnostic as _, Result};
+use age::{DecryptError, Decryptor, Encryptor, Identity, Recipient};
+use futures::{
+	io::{BufReader, Cursor},
+	AsyncBufReadExt as _, AsyncReadExt as _,
+};
+use miette::{miette, Context as _, IntoDiagnostic as _, Result};
 use tokio::io::AsyncWriteExt as _;
 use tokio_util::compat::{FuturesAsyncReadCompatExt as _, FuturesAsyncWriteCompatExt as _};
 use tracing::trace;
@@ -36,16 +40,29 @@ pub async fn encrypt_stream<R: tokio::io::AsyncRead + Unpin, W: futures::AsyncWr
 }
 
 /// Decrypt a bytestream given an [`Identity`].
+///
+/// If the identity doesn't match and the stream was encrypted to more than one recipient, this
+/// returns a specific error explaining that algae only supports single-recipient files.
 pub async fn decrypt_stream<R: futures::AsyncRead + Unpin, W: tokio::io::AsyncWrite + Unpin>(
 	reader: R,
 	mut writer: W,
 	key: Box<dyn Identity>,
 ) -> Result<u64> {
-	let mut decrypting_reader = Decryptor::new_async(reader)
+	let mut reader = BufReader::new(reader);
+	let (header, recipients) = read_header(&mut reader).await?;
+
+	let mut decrypting_reader = Decryptor::new_async(Cursor::new(header).chain(reader))
 		.await
 		.into_diagnostic()?
 		.decrypt_async(iter::once(&*key))
-		.into_diagnostic()?
+		.map_err(|err| match err {
+			DecryptError::NoMatchingKeys if recipients > 1 => miette!(
+				help = "algae only supports files encrypted to a single recipient; \
+					use age directly instead: `age --decrypt -i identity.txt file.age`",
+				"this file was encrypted to {recipients} recipients, and the given key doesn't match any of them"
+			),
+			err => miette::Report::from_err(err),
+		})?
 		.compat();
 
 	let bytes = tokio::io::copy(&mut decrypting_reader, &mut writer)
@@ -63,3 +80,208 @@ pub async fn decrypt_stream<R: futures::AsyncRead + Unpin, W: tokio::io::AsyncWr
 
 	Ok(bytes)
 }
+
+/// Re-encrypt a bytestream from an [`Identity`] to a new [`Recipient`].
+///
+/// The decrypted plaintext is piped straight into the encryptor, and never fully buffered in
+/// memory nor written anywhere else.
+pub async fn reencrypt_stream<R: futures::AsyncRead + Unpin, W: futures::AsyncWrite + Unpin>(
+	reader: R,
+	writer: W,
+	identity: Box<dyn Identity>,
+	recipient: Box<dyn Recipient + Send>,
+) -> Result<u64> {
+	let (plaintext_writer, plaintext_reader) = tokio::io::duplex(64 * 1024);
+
+	let (_, bytes) = futures::try_join!(
+		decrypt_stream(reader, plaintext_writer, identity),
+		encrypt_stream(plaintext_reader, writer, recipient),
+	)?;
+
+	trace!(?bytes, "bytestream re-encrypted");
+
+	Ok(bytes)
+}
+
+/// Upper bound on how much of the stream is buffered while inspecting the age header.
+const MAX_HEADER_LENGTH: u64 = 64 * 1024;
+
+/// Read the age header from the stream, counting the recipient stanzas.
+///
+/// Returns the bytes read (which must be replayed to the decryptor) and the number of recipients,
+/// not counting the "grease" stanzas that age adds to every file. This stops at the header MAC
+/// line, or at [`MAX_HEADER_LENGTH`] if the header is unexpectedly large, leaving the rest of the
+/// stream untouched.
+async fn read_header<R: futures::AsyncBufRead + Unpin>(reader: &mut R) -> Result<(Vec<u8>, usize)> {
+	let mut header = Vec::new();
+	let mut recipients = 0;
+
+	// bounded, so that a single unterminated line can't be buffered without limit
+	let mut reader = reader.take(MAX_HEADER_LENGTH);
+	loop {
+		let start = header.len();
+		let read = reader
+			.read_until(b'\n', &mut header)
+			.await
+			.into_diagnostic()
+			.wrap_err("reading the age header")?;
+		if read == 0 {
+			break;
+		}
+
+		let line = &header[start..];
+		if line.starts_with(b"---") {
+			break;
+		}
+
+		if let Some(stanza) = line.strip_prefix(b"-> ") {
+			let tag = stanza
+				.split(|b| b.is_ascii_whitespace())
+				.next()
+				.unwrap_or_default();
+			if !tag.ends_with(b"-grease") {
+				recipients += 1;
+			}
+		}
+	}
+
+	trace!(?recipients, length = header.len(), "age header inspected");
+	Ok((header, recipients))
+}
+
+#[cfg(test)]
+mod tests {
+	use age::x25519;
+
+	use super::*;
+
+	async fn encrypt_to(recipients: &[x25519::Recipient]) -> Vec<u8> {
+		let mut output = Vec::new();
+		let mut writer = Encryptor::with_recipients(recipients.iter().map(|r| r as _))
+			.unwrap()
+			.wrap_async_output(&mut output)
+			.await
+			.unwrap();
+		futures::AsyncWriteExt::write_all(&mut writer, b"hello world")
+			.await
+			.unwrap();
+		futures::AsyncWriteExt::close(&mut writer).await.unwrap();
+		output
+	}
+
+	#[tokio::test]
+	async fn decrypt_single_recipient() {
+		let id = x25519::Identity::generate();
+		let file = encrypt_to(&[id.to_public()]).await;
+
+		let (_, recipients) = read_header(&mut &file[..]).await.unwrap();
+		assert_eq!(recipients, 1);
+
+		let mut output = Vec::new();
+		decrypt_stream(&file[..], &mut output, Box::new(id))
+			.await
+			.unwrap();
+		assert_eq!(output, b"hello world");
+	}
+
+	#[tokio::test]
+	async fn read_header_is_bounded() {
+		let stream = vec![b'x'; MAX_HEADER_LENGTH as usize * 2];
+		let (header, recipients) = read_header(&mut &stream[..]).await.unwrap();
+		assert_eq!(header.len() as u64, MAX_HEADER_LENGTH);
+		assert_eq!(recipients, 0);
+	}
+
+	#[tokio::test]
+	async fn decrypt_single_recipient_wrong_key() {
+		let file = encrypt_to(&[x25519::Identity::generate().to_public()]).await;
+
+		let err = decrypt_stream(
+			&file[..],
+			tokio::io::sink(),
+			Box::new(x25519::Identity::generate()),
+		)
+		.await
+		.unwrap_err();
+		assert!(!err.to_string().contains("recipients"), "{err}");
+	}
+
+	#[tokio::test]
+	async fn decrypt_multi_recipient_wrong_key() {
+		let file = encrypt_to(&[
+			x25519::Identity::generate().to_public(),
+			x25519::Identity::generate().to_public(),
+		])
+		.await;
+
+		let err = decrypt_stream(
+			&file[..],
+			tokio::io::sink(),
+			Box::new(x25519::Identity::generate()),
+		)
+		.await
+		.unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"this file was encrypted to 2 recipients, and the given key doesn't match any of them"
+		);
+		assert!(err.help().is_some());
+	}
+
+	#[tokio::test]
+	async fn reencrypt_to_new_recipient() {
+		let old = x25519::Identity::generate();
+		let new = x25519::Identity::generate();
+		let file = encrypt_to(&[old.to_public()]).await;
+
+		let mut reencrypted = Vec::new();
+		let bytes = reencrypt_stream(
+			&file[..],
+			&mut reencrypted,
+			Box::new(old.clone()),
+			Box::new(new.to_public()),
+		)
+		.await
+		.unwrap();
+		assert_eq!(bytes, 11);
+
+		let mut output = Vec::new();
+		decrypt_stream(&reencrypted[..], &mut output, Box::new(new))
+			.await
+			.unwrap();
+		assert_eq!(output, b"hello world");
+
+		assert!(
+			decrypt_stream(&reencrypted[..], tokio::io::sink(), Box::new(old))
+				.await
+				.is_err()
+		);
+	}
+
+	#[tokio::test]
+	async fn reencrypt_wrong_key() {
+		let file = encrypt_to(&[x25519::Identity::generate().to_public()]).await;
+
+		let mut reencrypted = Vec::new();
+		assert!(reencrypt_stream(
+			&file[..],
+			&mut reencrypted,
+			Box::new(x25519::Identity::generate()),
+			Box::new(x25519::Identity::generate().to_public()),
+		)
+		.await
+		.is_err());
+	}
+
+	#[tokio::test]
+	async fn decrypt_multi_recipient_matching_key() {
+		let id = x25519::Identity::generate();
+		let file = encrypt_to(&[x25519::Identity::generate().to_public(), id.to_public()]).await;
+
+		let mut output = Vec::new();
+		decrypt_stream(&file[..], &mut output, Box::new(id))
+			.await
+			.unwrap();
+		assert_eq!(output, b"hello world");
+	}
+}
@@ -1,3 +1,5 @@
+use std::io::Write;
+
 use clap::{CommandFactory, Parser, ValueEnum};
 use clap_complete::{Generator, Shell};
 
@@ -37,24 +39,44 @@ pub enum ShellCompletion {
 }
 
 pub async fn run(ctx: Context<CompletionsArgs>) -> Result<()> {
-	fn generate(generator: impl Generator) {
+	generate(ctx.args_top.shell, &mut std::io::stdout());
+	Ok(())
+}
+
+fn generate(shell: ShellCompletion, out: &mut impl Write) {
+	fn generate_with(generator: impl Generator, out: &mut impl Write) {
 		let mut cmd = crate::args::Args::command();
-		clap_complete::generate(
-			generator,
-			&mut cmd,
-			env!("CARGO_PKG_NAME"),
-			&mut std::io::stdout(),
-		);
+		clap_complete::generate(generator, &mut cmd, env!("CARGO_PKG_NAME"), out);
 	}
 
-	match ctx.args_top.shell {
-		ShellCompletion::Bash => generate(Shell::Bash),
-		ShellCompletion::Elvish => generate(Shell::Elvish),
-		ShellCompletion::Fish => generate(Shell::Fish),
-		ShellCompletion::Nu => generate(clap_complete_nushell::Nushell),
-		ShellCompletion::Powershell => generate(Shell::PowerShell),
-		ShellCompletion::Zsh => generate(Shell::Zsh),
+	match shell {
+		ShellCompletion::Bash => generate_with(Shell::Bash, out),
+		ShellCompletion::Elvish => generate_with(Shell::Elvish, out),
+		ShellCompletion::Fish => generate_with(Shell::Fish, out),
+		ShellCompletion::Nu => generate_with(clap_complete_nushell::Nushell, out),
+		ShellCompletion::Powershell => generate_with(Shell::PowerShell, out),
+		ShellCompletion::Zsh => generate_with(Shell::Zsh, out),
 	}
+}
 
-	Ok(())
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn zsh_covers_nested_subcommands() {
+		let mut script = Vec::new();
+		generate(ShellCompletion::Zsh, &mut script);
+		let script = String::from_utf8(script).unwrap();
+
+		assert!(script.starts_with("#compdef bestool"));
+		#[cfg(feature = "caddy")]
+		assert!(script.contains("(caddy)"));
+		#[cfg(feature = "tamanu")]
+		assert!(script.contains("(tamanu)"));
+		#[cfg(feature = "tamanu-alerts")]
+		assert!(script.contains("_bestool__tamanu__alerts_commands"));
+		#[cfg(feature = "crypto")]
+		assert!(script.contains("(keygen)"));
+	}
 }
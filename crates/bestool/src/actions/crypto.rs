@@ -20,6 +20,9 @@ super::subcommands! {
 	encrypt => Encrypt(EncryptArgs),
 	hash => Hash(HashArgs),
 	keygen => Keygen(KeygenArgs),
+	#[cfg(feature = "crypto-aws")]
+	keygen_aws => KeygenAws(KeygenAwsArgs),
 	protect => Protect(ProtectArgs),
-	reveal => Reveal(RevealArgs)
+	reveal => Reveal(RevealArgs),
+	rotate => Rotate(RotateArgs)
 }
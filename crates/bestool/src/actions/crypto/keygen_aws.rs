@@ -0,0 +1,572 @@
+use std::path::Path;
+
+use algae_cli::cli::keygen::{self, KeygenArgs};
+use aws_sdk_ssm::types::ParameterType;
+use clap::{ArgGroup, Parser};
+use miette::{bail, Context as _, IntoDiagnostic as _, Result};
+use tokio::fs::remove_file;
+use tracing::{debug, info, warn};
+
+use super::CryptoArgs;
+use crate::{
+	actions::Context,
+	aws::{self, AwsArgs},
+};
+
+/// Generate an identity (key pair) and store the public key in AWS.
+///
+/// This generates a passphrase-protected identity file and a public key file exactly like
+/// `bestool crypto keygen`, and additionally uploads the public key to SSM Parameter Store and/or
+/// Secrets Manager under the given name(s). The secret key is never uploaded: keep the identity file
+/// safe locally.
+///
+/// The destinations are checked before the key pair is generated, so that bad credentials or an
+/// existing value don't leave unused key files behind. After uploading, the stored value is read
+/// back to verify it matches. If nothing could be stored, the generated files are removed; once the
+/// public key is stored, they're kept even if that verification fails.
+///
+/// The region and credentials are taken from the ambient AWS configuration, or from the `--aws-*`
+/// options.
+#[cfg_attr(docsrs, doc("\n\n**Command**: `bestool crypto keygen-aws`"))]
+#[derive(Debug, Clone, Parser)]
+#[command(group = ArgGroup::new("destination").required(true).multiple(true))]
+pub struct KeygenAwsArgs {
+	/// Name of the SSM parameter to store the public key in.
+	///
+	/// The parameter is created as a plain `String` parameter.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--ssm-name NAME`"))]
+	#[arg(long, value_name = "NAME", group = "destination")]
+	pub ssm_name: Option<String>,
+
+	/// Name of the Secrets Manager secret to store the public key in.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--secret-name NAME`"))]
+	#[arg(long, value_name = "NAME", group = "destination")]
+	pub secret_name: Option<String>,
+
+	/// Replace the value if the parameter or secret already exists.
+	///
+	/// By default this errors out instead of overwriting an existing value.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--overwrite`"))]
+	#[arg(long)]
+	pub overwrite: bool,
+
+	#[command(flatten)]
+	pub keygen: KeygenArgs,
+
+	#[command(flatten)]
+	pub aws: AwsArgs,
+}
+
+pub async fn run(ctx: Context<CryptoArgs, KeygenAwsArgs>) -> Result<()> {
+	let KeygenAwsArgs {
+		ssm_name,
+		secret_name,
+		overwrite,
+		keygen,
+		aws,
+	} = ctx.args_sub;
+
+	let aws = aws::init(&aws).await;
+	let ssm = ssm_name.map(|name| (aws_sdk_ssm::Client::new(&aws), name));
+	let secret = secret_name.map(|name| (aws_sdk_secretsmanager::Client::new(&aws), name));
+
+	if let Some((client, name)) = &ssm {
+		check_ssm(client, name, overwrite).await?;
+	}
+	if let Some((client, name)) = &secret {
+		check_secret(client, name, overwrite).await?;
+	}
+
+	let identity_path = keygen.identity_path();
+	let public_path = keygen.public_path.clone();
+	let public = keygen::generate(keygen).await?.to_string();
+
+	store(
+		ssm.as_ref(),
+		secret.as_ref(),
+		&public,
+		overwrite,
+		&identity_path,
+		&public_path,
+	)
+	.await
+}
+
+/// Upload the public key to each destination, then verify what was stored.
+///
+/// If the public key couldn't be stored anywhere, the key pair is removed. Once it's stored, the key
+/// pair is kept even if a later step fails, as anything encrypted to the stored public key would
+/// otherwise be lost.
+async fn store(
+	ssm: Option<&(aws_sdk_ssm::Client, String)>,
+	secret: Option<&(aws_sdk_secretsmanager::Client, String)>,
+	public: &str,
+	overwrite: bool,
+	identity_path: &Path,
+	public_path: &Path,
+) -> Result<()> {
+	if let Some((client, name)) = ssm {
+		if let Err(err) = store_in_ssm(client, name, public, overwrite).await {
+			remove_keypair(identity_path, public_path).await;
+			return Err(err);
+		}
+		info!(%name, "stored public key in SSM");
+	}
+
+	if let Some((client, name)) = secret {
+		if let Err(err) = store_in_secrets_manager(client, name, public, overwrite).await {
+			if ssm.is_some() {
+				warn!(path=?identity_path, "public key was stored in SSM only, keeping the identity");
+			} else {
+				remove_keypair(identity_path, public_path).await;
+			}
+			return Err(err);
+		}
+		info!(%name, "stored public key in Secrets Manager");
+	}
+
+	let verified = async {
+		if let Some((client, name)) = ssm {
+			verify_ssm(client, name, public).await?;
+		}
+		if let Some((client, name)) = secret {
+			verify_secret(client, name, public).await?;
+		}
+		Ok(())
+	}
+	.await;
+	if verified.is_err() {
+		warn!(path=?identity_path, "public key was stored but couldn't be verified, keeping the identity");
+	}
+	verified
+}
+
+/// Remove the files of a key pair whose public key couldn't be stored anywhere.
+async fn remove_keypair(identity_path: &Path, public_path: &Path) {
+	for path in [identity_path, public_path] {
+		if path.to_string_lossy() == "-" {
+			continue;
+		}
+
+		debug!(?path, "removing unused key file");
+		if let Err(err) = remove_file(path).await {
+			warn!(?path, "failed to remove unused key file: {err}");
+		}
+	}
+}
+
+/// Check that the SSM parameter can be read, and doesn't exist unless it's to be overwritten.
+async fn check_ssm(client: &aws_sdk_ssm::Client, name: &str, overwrite: bool) -> Result<()> {
+	match client.get_parameter().name(name).send().await {
+		Ok(_) if overwrite => Ok(()),
+		Ok(_) => bail!("SSM parameter {name} already exists, use --overwrite to replace it"),
+		Err(err)
+			if err
+				.as_service_error()
+				.is_some_and(|err| err.is_parameter_not_found()) =>
+		{
+			Ok(())
+		}
+		Err(err) => Err(err)
+			.into_diagnostic()
+			.wrap_err(format!("checking SSM parameter {name}")),
+	}
+}
+
+/// Check that the secret can be read, and doesn't exist unless it's to be overwritten.
+async fn check_secret(
+	client: &aws_sdk_secretsmanager::Client,
+	name: &str,
+	overwrite: bool,
+) -> Result<()> {
+	match client.describe_secret().secret_id(name).send().await {
+		Ok(_) if overwrite => Ok(()),
+		Ok(_) => bail!("secret {name} already exists, use --overwrite to replace it"),
+		Err(err)
+			if err
+				.as_service_error()
+				.is_some_and(|err| err.is_resource_not_found_exception()) =>
+		{
+			Ok(())
+		}
+		Err(err) => Err(err)
+			.into_diagnostic()
+			.wrap_err(format!("checking secret {name}")),
+	}
+}
+
+async fn store_in_ssm(
+	client: &aws_sdk_ssm::Client,
+	name: &str,
+	public: &str,
+	overwrite: bool,
+) -> Result<()> {
+	debug!(%name, "uploading public key to SSM");
+	client
+		.put_parameter()
+		.name(name)
+		.value(public)
+		.r#type(ParameterType::String)
+		.description("algae public key")
+		.overwrite(overwrite)
+		.send()
+		.await
+		.into_diagnostic()
+		.wrap_err(format!("storing public key in SSM parameter {name}"))?;
+
+	Ok(())
+}
+
+async fn verify_ssm(client: &aws_sdk_ssm::Client, name: &str, public: &str) -> Result<()> {
+	let stored = client
+		.get_parameter()
+		.name(name)
+		.send()
+		.await
+		.into_diagnostic()
+		.wrap_err(format!("reading back SSM parameter {name}"))?
+		.parameter
+		.and_then(|param| param.value);
+	if stored.as_deref() != Some(public) {
+		bail!("SSM parameter {name} doesn't contain the public key after upload");
+	}
+
+	Ok(())
+}
+
+async fn store_in_secrets_manager(
+	client: &aws_sdk_secretsmanager::Client,
+	name: &str,
+	public: &str,
+	overwrite: bool,
+) -> Result<()> {
+	debug!(%name, "uploading public key to Secrets Manager");
+	let created = client
+		.create_secret()
+		.name(name)
+		.secret_string(public)
+		.description("algae public key")
+		.send()
+		.await;
+	match created {
+		Ok(_) => {}
+		Err(err)
+			if overwrite
+				&& err
+					.as_service_error()
+					.is_some_and(|err| err.is_resource_exists_exception()) =>
+		{
+			debug!(%name, "secret exists, adding a new version");
+			client
+				.put_secret_value()
+				.secret_id(name)
+				.secret_string(public)
+				.send()
+				.await
+				.into_diagnostic()
+				.wrap_err(format!("storing public key in secret {name}"))?;
+		}
+		Err(err) => {
+			return Err(err)
+				.into_diagnostic()
+				.wrap_err(format!("storing public key in secret {name}"));
+		}
+	}
+
+	Ok(())
+}
+
+async fn verify_secret(
+	client: &aws_sdk_secretsmanager::Client,
+	name: &str,
+	public: &str,
+) -> Result<()> {
+	let stored = client
+		.get_secret_value()
+		.secret_id(name)
+		.send()
+		.await
+		.into_diagnostic()
+		.wrap_err(format!("reading back secret {name}"))?
+		.secret_string;
+	if stored.as_deref() != Some(public) {
+		bail!("secret {name} doesn't contain the public key after upload");
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use aws_sdk_secretsmanager::{
+		config::http::HttpResponse,
+		operation::{
+			create_secret::CreateSecretOutput, describe_secret::DescribeSecretOutput,
+			get_secret_value::GetSecretValueOutput, put_secret_value::PutSecretValueOutput,
+		},
+	};
+	use aws_sdk_ssm::{
+		operation::{get_parameter::GetParameterOutput, put_parameter::PutParameterOutput},
+		types::Parameter,
+	};
+	use aws_smithy_mocks::{
+		create_mock_http_client, mock, MockResponseInterceptor, Rule, RuleMode,
+	};
+
+	use super::*;
+
+	const PUBLIC: &str = "age1c3jdepjm05aey2dq9dgkfn4utj9a776zwqzqcar3879smuh04ysqttvmyd";
+
+	fn interceptor(rules: &[&Rule]) -> MockResponseInterceptor {
+		rules.iter().fold(
+			MockResponseInterceptor::new().rule_mode(RuleMode::MatchAny),
+			|interceptor, rule| interceptor.with_rule(rule),
+		)
+	}
+
+	fn ssm_client(rules: &[&Rule]) -> aws_sdk_ssm::Client {
+		aws_sdk_ssm::Client::from_conf(
+			aws_sdk_ssm::Config::builder()
+				.with_test_defaults()
+				.region(aws_sdk_ssm::config::Region::from_static("ap-southeast-2"))
+				.http_client(create_mock_http_client())
+				.interceptor(interceptor(rules))
+				.build(),
+		)
+	}
+
+	fn secrets_client(rules: &[&Rule]) -> aws_sdk_secretsmanager::Client {
+		aws_sdk_secretsmanager::Client::from_conf(
+			aws_sdk_secretsmanager::Config::builder()
+				.with_test_defaults()
+				.region(aws_sdk_secretsmanager::config::Region::from_static(
+					"ap-southeast-2",
+				))
+				.http_client(create_mock_http_client())
+				.interceptor(interceptor(rules))
+				.build(),
+		)
+	}
+
+	fn resource_exists() -> HttpResponse {
+		HttpResponse::new(
+			400.try_into().unwrap(),
+			r#"{"__type":"ResourceExistsException","Message":"the secret already exists"}"#.into(),
+		)
+	}
+
+	fn not_found(kind: &str) -> HttpResponse {
+		HttpResponse::new(
+			400.try_into().unwrap(),
+			format!(r#"{{"__type":"{kind}","Message":"not found"}}"#).into(),
+		)
+	}
+
+	fn access_denied() -> HttpResponse {
+		HttpResponse::new(
+			400.try_into().unwrap(),
+			r#"{"__type":"AccessDeniedException","Message":"not authorised"}"#.into(),
+		)
+	}
+
+	fn parse(args: &[&str]) -> Result<KeygenAwsArgs, clap::Error> {
+		KeygenAwsArgs::try_parse_from(["keygen-aws"].iter().chain(args))
+	}
+
+	#[test]
+	fn args_require_a_destination() {
+		assert!(parse(&[]).is_err());
+		assert!(parse(&["--overwrite"]).is_err());
+	}
+
+	#[test]
+	fn args_destinations() {
+		let args = parse(&["--ssm-name", "/bes/backups/key"]).unwrap();
+		assert_eq!(args.ssm_name.as_deref(), Some("/bes/backups/key"));
+		assert_eq!(args.secret_name, None);
+
+		let args = parse(&["--ssm-name", "one", "--secret-name", "two", "-R"]).unwrap();
+		assert_eq!(args.ssm_name.as_deref(), Some("one"));
+		assert_eq!(args.secret_name.as_deref(), Some("two"));
+		assert!(args.keygen.random_passphrase);
+	}
+
+	#[tokio::test]
+	async fn ssm_put_and_verify() {
+		let put = mock!(aws_sdk_ssm::Client::put_parameter)
+			.match_requests(|req| {
+				req.name() == Some("/bes/key")
+					&& req.value() == Some(PUBLIC)
+					&& req.overwrite() == Some(false)
+			})
+			.then_output(|| PutParameterOutput::builder().version(1).build());
+		let get = mock!(aws_sdk_ssm::Client::get_parameter).then_output(|| {
+			GetParameterOutput::builder()
+				.parameter(Parameter::builder().value(PUBLIC).build())
+				.build()
+		});
+		let client = ssm_client(&[&put, &get]);
+
+		store_in_ssm(&client, "/bes/key", PUBLIC, false)
+			.await
+			.unwrap();
+		verify_ssm(&client, "/bes/key", PUBLIC).await.unwrap();
+		assert_eq!(put.num_calls(), 1);
+		assert_eq!(get.num_calls(), 1);
+	}
+
+	#[tokio::test]
+	async fn ssm_verify_mismatch() {
+		let put = mock!(aws_sdk_ssm::Client::put_parameter)
+			.then_output(|| PutParameterOutput::builder().version(1).build());
+		let get = mock!(aws_sdk_ssm::Client::get_parameter).then_output(|| {
+			GetParameterOutput::builder()
+				.parameter(Parameter::builder().value("something else").build())
+				.build()
+		});
+		let ssm = (ssm_client(&[&put, &get]), "/bes/key".to_string());
+
+		let dir = tempfile::tempdir().unwrap();
+		let identity = dir.path().join("identity.txt.age");
+		let public = dir.path().join("identity.pub");
+		std::fs::write(&identity, "secret").unwrap();
+		std::fs::write(&public, PUBLIC).unwrap();
+
+		let err = store(Some(&ssm), None, PUBLIC, false, &identity, &public)
+			.await
+			.unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"SSM parameter /bes/key doesn't contain the public key after upload"
+		);
+		assert_eq!(put.num_calls(), 1);
+		assert!(identity.exists());
+		assert!(public.exists());
+	}
+
+	#[tokio::test]
+	async fn ssm_put_failure_removes_keypair() {
+		let put = mock!(aws_sdk_ssm::Client::put_parameter).then_http_response(access_denied);
+		let ssm = (ssm_client(&[&put]), "/bes/key".to_string());
+
+		let dir = tempfile::tempdir().unwrap();
+		let identity = dir.path().join("identity.txt.age");
+		let public = dir.path().join("identity.pub");
+		std::fs::write(&identity, "secret").unwrap();
+		std::fs::write(&public, PUBLIC).unwrap();
+
+		assert!(store(Some(&ssm), None, PUBLIC, false, &identity, &public)
+			.await
+			.is_err());
+		assert!(!identity.exists());
+		assert!(!public.exists());
+	}
+
+	#[tokio::test]
+	async fn secret_exists_overwrite() {
+		let create = mock!(aws_sdk_secretsmanager::Client::create_secret)
+			.then_http_response(resource_exists);
+		let put = mock!(aws_sdk_secretsmanager::Client::put_secret_value)
+			.match_requests(|req| req.secret_string() == Some(PUBLIC))
+			.then_output(|| PutSecretValueOutput::builder().build());
+		let get = mock!(aws_sdk_secretsmanager::Client::get_secret_value).then_output(|| {
+			GetSecretValueOutput::builder()
+				.secret_string(PUBLIC)
+				.build()
+		});
+		let client = secrets_client(&[&create, &put, &get]);
+
+		store_in_secrets_manager(&client, "bes-key", PUBLIC, true)
+			.await
+			.unwrap();
+		verify_secret(&client, "bes-key", PUBLIC).await.unwrap();
+		assert_eq!(put.num_calls(), 1);
+	}
+
+	#[tokio::test]
+	async fn secret_exists_no_overwrite() {
+		let create = mock!(aws_sdk_secretsmanager::Client::create_secret)
+			.then_http_response(resource_exists);
+		let put = mock!(aws_sdk_secretsmanager::Client::put_secret_value)
+			.then_output(|| PutSecretValueOutput::builder().build());
+		let client = secrets_client(&[&create, &put]);
+
+		assert!(store_in_secrets_manager(&client, "bes-key", PUBLIC, false)
+			.await
+			.is_err());
+		assert_eq!(put.num_calls(), 0);
+	}
+
+	#[tokio::test]
+	async fn secret_created() {
+		let create = mock!(aws_sdk_secretsmanager::Client::create_secret)
+			.match_requests(|req| req.name() == Some("bes-key"))
+			.then_output(|| CreateSecretOutput::builder().build());
+		let get = mock!(aws_sdk_secretsmanager::Client::get_secret_value).then_output(|| {
+			GetSecretValueOutput::builder()
+				.secret_string(PUBLIC)
+				.build()
+		});
+		let client = secrets_client(&[&create, &get]);
+
+		store_in_secrets_manager(&client, "bes-key", PUBLIC, false)
+			.await
+			.unwrap();
+		verify_secret(&client, "bes-key", PUBLIC).await.unwrap();
+		assert_eq!(create.num_calls(), 1);
+	}
+
+	#[tokio::test]
+	async fn ssm_check() {
+		let missing = mock!(aws_sdk_ssm::Client::get_parameter)
+			.then_http_response(|| not_found("ParameterNotFound"));
+		check_ssm(&ssm_client(&[&missing]), "/bes/key", false)
+			.await
+			.unwrap();
+
+		let exists = mock!(aws_sdk_ssm::Client::get_parameter).then_output(|| {
+			GetParameterOutput::builder()
+				.parameter(Parameter::builder().value(PUBLIC).build())
+				.build()
+		});
+		check_ssm(&ssm_client(&[&exists]), "/bes/key", true)
+			.await
+			.unwrap();
+		let err = check_ssm(&ssm_client(&[&exists]), "/bes/key", false)
+			.await
+			.unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"SSM parameter /bes/key already exists, use --overwrite to replace it"
+		);
+
+		let denied = mock!(aws_sdk_ssm::Client::get_parameter).then_http_response(access_denied);
+		assert!(check_ssm(&ssm_client(&[&denied]), "/bes/key", true)
+			.await
+			.is_err());
+	}
+
+	#[tokio::test]
+	async fn secret_check() {
+		let missing = mock!(aws_sdk_secretsmanager::Client::describe_secret)
+			.then_http_response(|| not_found("ResourceNotFoundException"));
+		check_secret(&secrets_client(&[&missing]), "bes-key", false)
+			.await
+			.unwrap();
+
+		let exists = mock!(aws_sdk_secretsmanager::Client::describe_secret)
+			.then_output(|| DescribeSecretOutput::builder().name("bes-key").build());
+		check_secret(&secrets_client(&[&exists]), "bes-key", true)
+			.await
+			.unwrap();
+		assert!(check_secret(&secrets_client(&[&exists]), "bes-key", false)
+			.await
+			.is_err());
+
+		let denied = mock!(aws_sdk_secretsmanager::Client::describe_secret)
+			.then_http_response(access_denied);
+		assert!(check_secret(&secrets_client(&[&denied]), "bes-key", true)
+			.await
+			.is_err());
+	}
+}
@@ -0,0 +1,376 @@
+use std::{
+	ffi::{OsStr, OsString},
+	iter,
+	path::{Path, PathBuf},
+	rc::Rc,
+};
+
+use age::{DecryptError, Decryptor, Identity};
+use age_core::format::{FileKey, Stanza};
+use algae_cli::{
+	files::reencrypt_file,
+	keys::{parse_id_as_identity, parse_id_as_recipient, KeyArgs},
+	streams::decrypt_stream,
+};
+use clap::{ArgGroup, Parser};
+use miette::{bail, Context as _, IntoDiagnostic as _, Result};
+use tokio::fs::{read_to_string, remove_file, rename, File, OpenOptions};
+use tokio_util::compat::TokioAsyncReadCompatExt as _;
+use tracing::{debug, info, instrument};
+use walkdir::WalkDir;
+
+use super::CryptoArgs;
+use crate::actions::Context;
+
+/// Re-encrypt a directory of encrypted files to a new key.
+///
+/// This walks the directory and its subdirectories for `.age` files, and re-encrypts each one from
+/// the current secret key to a new public key, without ever writing the plaintext to disk. This is
+/// useful to rotate the key that protects an archive of backups.
+///
+/// Each file is re-encrypted alongside the original, and only replaces it once that has completed,
+/// so an interruption never leaves a file half-written. Files that can't be decrypted with the
+/// current key, such as those already rotated, are skipped: it's safe to run the same rotation
+/// again to pick up where it left off.
+///
+/// With `--verify-key-path`, each re-encrypted file is also decrypted with the new key before it
+/// replaces the original.
+///
+/// Either of `--key-path` or `--key` must be provided for the current secret key, and either of
+/// `--recipient-path` or `--recipient` for the new public key.
+#[cfg_attr(docsrs, doc("\n\n**Command**: `bestool crypto rotate`"))]
+#[derive(Debug, Clone, Parser)]
+#[command(group = ArgGroup::new("to").required(true))]
+pub struct RotateArgs {
+	/// Directory containing the files to re-encrypt.
+	#[cfg_attr(docsrs, doc("\n\n**Argument**: path"))]
+	pub dir: PathBuf,
+
+	/// Path to the public key (or identity) file of the new recipient.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--recipient-path PATH`"))]
+	#[arg(long, group = "to")]
+	pub recipient_path: Option<PathBuf>,
+
+	/// The public key of the new recipient as a string.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `-r, --recipient KEY`"))]
+	#[arg(short, long, group = "to")]
+	pub recipient: Option<String>,
+
+	/// Path to the identity file of the new key, to check re-encrypted files with.
+	///
+	/// This must be a plain identity file or secret key, not a passphrase-protected one.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--verify-key-path PATH`"))]
+	#[arg(long)]
+	pub verify_key_path: Option<PathBuf>,
+
+	#[command(flatten)]
+	pub key: KeyArgs,
+}
+
+pub async fn run(ctx: Context<CryptoArgs, RotateArgs>) -> Result<()> {
+	let RotateArgs {
+		dir,
+		recipient_path,
+		recipient,
+		verify_key_path,
+		key,
+	} = ctx.args_sub;
+
+	// clap requires one of these, so without a path there's a key
+	let recipient = match recipient_path {
+		Some(path) => read_to_string(&path)
+			.await
+			.into_diagnostic()
+			.wrap_err("reading recipient file")?,
+		None => recipient.unwrap_or_default(),
+	};
+	// fail early rather than on every file
+	parse_id_as_recipient(&recipient)?;
+
+	let verify = match verify_key_path {
+		Some(path) => Some(parse_id_as_identity(
+			&read_to_string(&path)
+				.await
+				.into_diagnostic()
+				.wrap_err("reading verification identity file")?,
+		)?),
+		None => None,
+	};
+
+	let identity = key.require_secret_key().await?;
+	let summary = rotate_dir(&dir, identity, &recipient, verify).await?;
+	println!(
+		"rotated {}, skipped {}, failed {}",
+		summary.rotated, summary.skipped, summary.failed
+	);
+
+	if summary.failed > 0 {
+		bail!("{} files failed to rotate", summary.failed);
+	}
+
+	Ok(())
+}
+
+/// How many files were handled which way during a rotation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Summary {
+	rotated: usize,
+	skipped: usize,
+	failed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+	Rotated,
+	Skipped,
+}
+
+/// An identity that can be handed to the re-encryption of each file in turn.
+///
+/// The algae functions take ownership of a boxed identity, but the current key should only be
+/// read (and its passphrase asked for) once per rotation.
+#[derive(Clone)]
+struct SharedIdentity(Rc<dyn Identity>);
+
+impl Identity for SharedIdentity {
+	fn unwrap_stanza(&self, stanza: &Stanza) -> Option<Result<FileKey, DecryptError>> {
+		self.0.unwrap_stanza(stanza)
+	}
+
+	fn unwrap_stanzas(&self, stanzas: &[Stanza]) -> Option<Result<FileKey, DecryptError>> {
+		self.0.unwrap_stanzas(stanzas)
+	}
+}
+
+async fn rotate_dir(
+	dir: &Path,
+	identity: Box<dyn Identity>,
+	recipient: &str,
+	verify: Option<Box<dyn Identity>>,
+) -> Result<Summary> {
+	let identity = SharedIdentity(Rc::from(identity));
+	let verify = verify.map(|id| SharedIdentity(Rc::from(id)));
+
+	let mut summary = Summary::default();
+	for entry in WalkDir::new(dir).sort_by_file_name() {
+		let entry = entry.into_diagnostic().wrap_err("walking the directory")?;
+		let path = entry.path();
+		if !entry.file_type().is_file() || path.extension() != Some(OsStr::new("age")) {
+			continue;
+		}
+
+		match rotate_file(path, &identity, recipient, verify.as_ref()).await {
+			Ok(Outcome::Rotated) => {
+				info!(?path, "rotated");
+				summary.rotated += 1;
+			}
+			Ok(Outcome::Skipped) => {
+				info!(?path, "not encrypted to the current key, skipping");
+				summary.skipped += 1;
+			}
+			Err(err) => {
+				eprintln!("{path:?}\t{err:?}");
+				summary.failed += 1;
+			}
+		}
+	}
+
+	Ok(summary)
+}
+
+#[instrument(level = "debug", skip(identity, recipient, verify))]
+async fn rotate_file(
+	path: &Path,
+	identity: &SharedIdentity,
+	recipient: &str,
+	verify: Option<&SharedIdentity>,
+) -> Result<Outcome> {
+	if !decrypts_with(path, identity).await? {
+		return Ok(Outcome::Skipped);
+	}
+
+	let mut temporary = OsString::from(path.as_os_str());
+	temporary.push(".new");
+	let temporary = PathBuf::from(temporary);
+	if temporary.exists() {
+		debug!(?temporary, "removing leftover from an interrupted rotation");
+		remove_file(&temporary)
+			.await
+			.into_diagnostic()
+			.wrap_err("removing leftover re-encrypted file")?;
+	}
+
+	if let Err(err) = reencrypt_file(
+		path,
+		&temporary,
+		Box::new(identity.clone()),
+		parse_id_as_recipient(recipient)?,
+	)
+	.await
+	{
+		remove_file(&temporary).await.ok();
+		return Err(err);
+	}
+
+	if let Some(verify) = verify {
+		if let Err(err) = check_decrypts(&temporary, verify).await {
+			remove_file(&temporary).await.ok();
+			return Err(err.wrap_err("verifying the re-encrypted file with the new key"));
+		}
+	}
+
+	// make sure the new contents are on disk before the original is replaced
+	if let Err(err) = sync_file(&temporary).await {
+		remove_file(&temporary).await.ok();
+		return Err(err);
+	}
+
+	rename(&temporary, path)
+		.await
+		.into_diagnostic()
+		.wrap_err("replacing the original file")?;
+
+	Ok(Outcome::Rotated)
+}
+
+async fn sync_file(path: &Path) -> Result<()> {
+	// opened for writing, as Windows can't flush a file opened read-only
+	OpenOptions::new()
+		.write(true)
+		.open(path)
+		.await
+		.into_diagnostic()
+		.wrap_err("opening the re-encrypted file")?
+		.sync_all()
+		.await
+		.into_diagnostic()
+		.wrap_err("syncing the re-encrypted file to disk")
+}
+
+/// Check whether the file is encrypted to the identity, only reading its header.
+async fn decrypts_with(path: &Path, identity: &SharedIdentity) -> Result<bool> {
+	let file = File::open(path)
+		.await
+		.into_diagnostic()
+		.wrap_err("opening the file")?;
+	let decryptor = Decryptor::new_async(file.compat())
+		.await
+		.into_diagnostic()
+		.wrap_err("reading the age header")?;
+	match decryptor.decrypt_async(iter::once(identity as &dyn Identity)) {
+		Ok(_) => Ok(true),
+		Err(DecryptError::NoMatchingKeys) => Ok(false),
+		Err(err) => Err(err).into_diagnostic(),
+	}
+}
+
+async fn check_decrypts(path: &Path, identity: &SharedIdentity) -> Result<()> {
+	let file = File::open(path)
+		.await
+		.into_diagnostic()
+		.wrap_err("opening the re-encrypted file")?;
+	decrypt_stream(file.compat(), tokio::io::sink(), Box::new(identity.clone())).await?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use age::x25519;
+
+	use super::*;
+
+	fn write_encrypted(path: &Path, plaintext: &[u8], recipient: &x25519::Identity) {
+		fs::write(
+			path,
+			age::encrypt(&recipient.to_public(), plaintext).unwrap(),
+		)
+		.unwrap();
+	}
+
+	fn read_encrypted(path: &Path, identity: &x25519::Identity) -> Vec<u8> {
+		age::decrypt(identity, &fs::read(path).unwrap()).unwrap()
+	}
+
+	#[tokio::test]
+	async fn rotates_a_directory() {
+		let dir = tempfile::tempdir().unwrap();
+		let old = x25519::Identity::generate();
+		let new = x25519::Identity::generate();
+
+		fs::create_dir(dir.path().join("sub")).unwrap();
+		write_encrypted(&dir.path().join("one.age"), b"one", &old);
+		write_encrypted(&dir.path().join("sub/two.age"), b"two", &old);
+		write_encrypted(&dir.path().join("three.age"), b"three", &new);
+		fs::write(dir.path().join("notes.txt"), "not encrypted").unwrap();
+
+		let summary = rotate_dir(
+			dir.path(),
+			Box::new(old.clone()),
+			&new.to_public().to_string(),
+			Some(Box::new(new.clone())),
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			summary,
+			Summary {
+				rotated: 2,
+				skipped: 1,
+				failed: 0
+			}
+		);
+
+		assert_eq!(read_encrypted(&dir.path().join("one.age"), &new), b"one");
+		assert_eq!(
+			read_encrypted(&dir.path().join("sub/two.age"), &new),
+			b"two"
+		);
+		assert_eq!(
+			read_encrypted(&dir.path().join("three.age"), &new),
+			b"three"
+		);
+		assert!(!dir.path().join("one.age.new").exists());
+
+		// running it again is safe and does nothing
+		let summary = rotate_dir(
+			dir.path(),
+			Box::new(old),
+			&new.to_public().to_string(),
+			None,
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			summary,
+			Summary {
+				rotated: 0,
+				skipped: 3,
+				failed: 0
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn failed_verification_keeps_the_original() {
+		let dir = tempfile::tempdir().unwrap();
+		let old = x25519::Identity::generate();
+		let new = x25519::Identity::generate();
+		let wrong = x25519::Identity::generate();
+		write_encrypted(&dir.path().join("one.age"), b"one", &old);
+
+		let summary = rotate_dir(
+			dir.path(),
+			Box::new(old.clone()),
+			&new.to_public().to_string(),
+			Some(Box::new(wrong)),
+		)
+		.await
+		.unwrap();
+		assert_eq!(summary.failed, 1);
+
+		assert_eq!(read_encrypted(&dir.path().join("one.age"), &old), b"one");
+		assert!(!dir.path().join("one.age.new").exists());
+	}
+}
@@ -10,7 +10,7 @@ use std::{
 use clap::{Parser, Subcommand};
 use embedded_graphics::Drawable;
 use miette::{miette, IntoDiagnostic, Result, WrapErr};
-use rpi_st7789v2_driver::{DriverArgs, Driver};
+use rpi_st7789v2_driver::{DriverArgs, Driver, Rotation, SimpleImage};
 use tracing::{error, info, instrument, trace};
 
 use super::ItiArgs;
@@ -37,6 +37,12 @@ pub struct LcdArgs {
 	#[arg(long, default_value = "18")]
 	pub backlight: u8,
 
+	/// Hardware PWM channel wired to the backlight pin, for brightness control.
+	///
+	/// Without this, the backlight is only switched on and off.
+	#[arg(long, value_parser = clap::value_parser!(u8).range(0..=3))]
+	pub backlight_pwm: Option<u8>,
+
 	/// GPIO pin number for the display's reset pin.
 	#[arg(long, default_value = "27")]
 	pub reset: u8,
@@ -53,6 +59,10 @@ pub struct LcdArgs {
 	#[arg(long, default_value = "20000000")]
 	pub frequency: u32,
 
+	/// Rotation of the display in degrees, clockwise.
+	#[arg(long, value_enum, default_value = "0")]
+	pub rotation: Rotation,
+
 	/// ZMQ socket to use for JSON screen updates.
 	#[arg(default_value = "tcp://[::1]:2009")]
 	pub zmq_socket: String,
@@ -67,10 +77,14 @@ impl From<LcdArgs> for DriverArgs {
 		DriverArgs {
 			spi: args.spi,
 			backlight: args.backlight,
+			backlight_pwm: args.backlight_pwm,
 			reset: args.reset,
 			dc: args.dc,
 			ce: args.ce,
 			frequency: args.frequency,
+			// probed at startup
+			chunk_size: DriverArgs::default().chunk_size,
+			rotation: args.rotation,
 		}
 	}
 }
@@ -122,7 +136,8 @@ pub enum LcdAction {
 
 	/// Turn the display on.
 	///
-	/// This wakes the display, turns on the backlight, and shows the current screen contents.
+	/// This wakes the display, turns on the backlight, and shows the current screen contents,
+	/// including any updates sent while it was off.
 	///
 	/// The LCD must then rest for 120ms before any further commands can be sent.
 	///
@@ -132,7 +147,8 @@ pub enum LcdAction {
 
 	/// Turn the display off.
 	///
-	/// This turns off the backlight and puts the display to sleep, which uses less power.
+	/// This turns off the backlight and puts the display to sleep, which uses less power. Updates
+	/// sent while the display is off are drawn when it's turned back on.
 	///
 	/// The LCD must then rest for 5ms before any further commands can be sent.
 	///
@@ -194,8 +210,13 @@ pub fn serve(ctx: Context<ItiArgs, LcdArgs>) -> Result<()> {
 	lcd.init()?;
 	lcd.probe_buffer_length()?;
 
+	// drawing is a no-op while the display is off, so everything is also drawn to a copy of the
+	// screen, which is shown on wake if it changed meanwhile
+	let mut offscreen = lcd.image();
+	let mut stale = false;
+
 	loop {
-		match loop_inner(running.clone(), &socket, &mut lcd) {
+		match loop_inner(running.clone(), &socket, &mut lcd, &mut offscreen, &mut stale) {
 			Ok(ControlFlow::Continue(_)) => continue,
 			Ok(ControlFlow::Break(_)) => break,
 			Err(err) => {
@@ -210,11 +231,13 @@ pub fn serve(ctx: Context<ItiArgs, LcdArgs>) -> Result<()> {
 	Ok(())
 }
 
-#[instrument(level = "trace", skip(socket, lcd))]
+#[instrument(level = "trace", skip(socket, lcd, offscreen))]
 fn loop_inner(
 	running: Arc<AtomicBool>,
 	socket: &zmq::Socket,
 	lcd: &mut Driver,
+	offscreen: &mut SimpleImage,
+	stale: &mut bool,
 ) -> Result<ControlFlow<()>> {
 	let mut polls = [socket.as_poll_item(zmq::POLLIN)];
 	let polled = zmq::poll(&mut polls, 1000)
@@ -244,19 +267,28 @@ fn loop_inner(
 	match screen {
 		Light(true) => {
 			info!("turning screen on");
-			lcd.display(true)?;
-			lcd.backlight(true);
 			lcd.wake()?;
+			if std::mem::take(stale) {
+				info!("drawing updates received while the screen was off");
+				lcd.print((0, 0), offscreen)?;
+			}
 		}
 		Light(false) => {
 			info!("turning screen off");
-			lcd.display(false)?;
-			lcd.backlight(false);
 			lcd.sleep()?;
 		}
 		otherwise => {
-			info!("updating screen {otherwise:?}");
-			otherwise.draw(lcd)?;
+			otherwise
+				.draw(offscreen)
+				.into_diagnostic()
+				.wrap_err("offscreen: draw")?;
+			if lcd.is_awake() {
+				info!("updating screen {otherwise:?}");
+				otherwise.draw(lcd)?;
+			} else {
+				info!("screen is off, will update when turned on {otherwise:?}");
+				*stale = true;
+			}
 		}
 	}
 
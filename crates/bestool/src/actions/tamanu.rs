@@ -175,7 +175,7 @@ pub fn find_postgres_bin(name: &str) -> Result<OsString> {
 		let exec_file_name = if cfg!(windows) {
 			format!("{name}.exe")
 		} else {
-			format!("{name}")
+			name.to_string()
 		};
 		Ok([root, version.as_str(), "bin", &exec_file_name]
 			.iter()
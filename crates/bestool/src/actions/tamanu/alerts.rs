@@ -1,14 +1,22 @@
 use std::{
-	collections::HashMap, error::Error, io::Write, ops::ControlFlow, path::PathBuf, process,
-	time::Duration,
+	collections::{BTreeMap, HashMap, HashSet},
+	error::Error,
+	fs::{File, OpenOptions},
+	hash::{DefaultHasher, Hash as _, Hasher as _},
+	io::Write,
+	ops::ControlFlow,
+	path::{Path, PathBuf},
+	process,
+	time::{Duration, Instant},
 };
 
 use bytes::{BufMut, BytesMut};
 use chrono::{DateTime, Utc};
 use clap::Parser;
 use folktime::duration::{Duration as Folktime, Style as FolkStyle};
+use fs4::fs_std::FileExt;
 use mailgun_rs::{EmailAddress, Mailgun, Message};
-use miette::{miette, Context as _, IntoDiagnostic, Result};
+use miette::{bail, miette, Context as _, IntoDiagnostic, Result};
 use reqwest::Url;
 use serde_json::json;
 use sysinfo::System;
@@ -22,6 +30,11 @@ use crate::{actions::Context, postgres_to_value::rows_to_value_map};
 
 use super::{config::load_config, find_package, find_tamanu, TamanuArgs};
 
+mod retry;
+mod templates;
+
+use retry::{RetryOverride, RetryPolicy};
+
 const DEFAULT_SUBJECT_TEMPLATE: &str = "[Tamanu Alert] {{ filename }} ({{ hostname }})";
 
 /// Execute alert definitions against Tamanu.
@@ -63,10 +76,57 @@ const DEFAULT_SUBJECT_TEMPLATE: &str = "[Tamanu Alert] {{ filename }} ({{ hostna
 /// - `hostname`: the hostname of the machine running this command
 /// - `filename`: the name of the alert definition file
 /// - `now`: the current date and time
+/// - `severity`: the severity of the alert, e.g. for a subject prefix
 ///
 /// Additionally you can `{% include "subject" %}` to include the rendering of
 /// the subject template in the email template.
 ///
+/// ## Helpers
+///
+/// On top of Tera's [built-in filters](https://keats.github.io/tera/docs/#built-in-filters),
+/// these are available in all templates. They render null values as nothing.
+///
+/// - `humanize_number(decimals=0, separator=",")`: format a number with
+///   thousands separators, rounded to `decimals` places: `1234567` becomes
+///   `1,234,567`. Numbers in strings, like `numeric` columns, are rounded
+///   exactly.
+/// - `format_date(format="%Y-%m-%d %H:%M", timezone="UTC")`: format a date or
+///   timestamp with a [strftime](https://docs.rs/chrono/latest/chrono/format/strftime/)
+///   `format`, in an IANA `timezone` such as `Pacific/Auckland`.
+/// - `truncate_chars(length=255, end="…")`: shorten a value to at most
+///   `length` characters. Unlike Tera's `truncate`, numbers and other values
+///   are shortened too.
+///
+/// ```yaml
+/// template: |
+///   {{ rows | length | humanize_number }} jobs failed, the first at
+///   {{ rows.0.created_at | format_date(timezone="Pacific/Auckland") }}:
+///   {{ rows.0.error | truncate_chars(length=100) }}
+/// ```
+///
+/// ## Partials
+///
+/// Common fragments (e.g. email headers and footers) can be written once in a
+/// `_templates` folder in any of the `--dir`s, and included by name (the path
+/// within `_templates`, without extension) from any template:
+///
+/// ```yaml
+/// send:
+/// - target: email
+///   addresses: [alerts@tamanu.io]
+///   template: |
+///     {% include "header" %}
+///     <p>There are {{ rows | length }} rows.</p>
+///     {% include "footer" %}
+/// ```
+///
+/// Partials can include each other, but not in a cycle. Including a partial that
+/// doesn't exist is an error, reported when the alert is loaded.
+///
+/// A partial with errors (which doesn't parse, includes one that doesn't exist,
+/// or is part of a cycle) is reported and left out, and only the alerts which
+/// include it, directly or through other partials, are skipped.
+///
 /// # Sources
 ///
 /// Each alert must have one source that it executes to determine whether the
@@ -82,6 +142,20 @@ const DEFAULT_SUBJECT_TEMPLATE: &str = "[Tamanu Alert] {{ filename }} ({{ hostna
 ///   SELECT 1 + 1
 /// ```
 ///
+/// ### Row count threshold
+///
+/// To only trigger past a certain number of rows, set `fire_when_rows` to a
+/// comparison against the row count, one of `>`, `>=`, `==`, `!=`, `<=`, `<`.
+/// A bare number is the same as `>=` that number. The default is `>= 1`.
+///
+/// ```yaml
+/// sql: |
+///   SELECT * FROM fhir.jobs WHERE status = 'Errored'
+/// fire_when_rows: "> 5"
+/// ```
+///
+/// This can also be used to alert when something is missing, with `== 0`.
+///
 /// ### Query binding parameters
 ///
 /// The SQL query will be passed exactly the number of parameters it expects.
@@ -103,11 +177,76 @@ const DEFAULT_SUBJECT_TEMPLATE: &str = "[Tamanu Alert] {{ filename }} ({{ hostna
 ///   exit 1
 /// ```
 ///
+/// # Severity
+///
+/// Alerts have a `severity` of `info`, `warning` (the default), or `critical`.
+/// It's available to templates, and can be used to route alerts to different
+/// external targets (see below).
+///
+/// ```yaml
+/// severity: critical
+/// subject: "[{{ severity | upper }}] Sync is down"
+/// ```
+///
+/// # Interval
+///
+/// Alerts run every time this command does, and look back over `--interval`.
+/// An alert can set its own `interval` to run less often: with `--state-file`,
+/// it's skipped until its interval has passed since it last ran, give or take
+/// half of `--interval` to allow for the scheduler's timing. Its queries also
+/// look back over its own interval.
+///
+/// ```yaml
+/// sql: |
+///   SELECT * FROM fhir.jobs WHERE status = 'Errored'
+/// interval: 1d
+/// ```
+///
+/// `--min-interval` sets a floor on how often alerts run, to protect a busy
+/// database. Intervals shorter than that are raised to it, with a warning.
+///
+/// # Cooldown
+///
+/// An alert whose condition persists fires on every run. To avoid repeating the
+/// same notification, set a `cooldown`: once an alert is sent to a target, it
+/// won't be sent to that target again until the cooldown has passed. Targets
+/// that it failed to send to are tried again on the next run. This needs
+/// `--state-file` to remember when alerts were last sent between runs.
+///
+/// By default any firing is a repeat. With a `dedup_key`, a template rendered
+/// with the same variables as the alert, only firings with the same key are
+/// repeats, and a different key is sent straight away:
+///
+/// ```yaml
+/// sql: |
+///   SELECT id FROM fhir.jobs WHERE status = 'Errored'
+/// cooldown: 6h
+/// dedup_key: "{% for row in rows %}{{ row.id }},{% endfor %}"
+/// ```
+///
 /// # Send targets
 ///
 /// You can send triggered alerts to one or more different targets. Current send
-/// targets are: `email`, `zendesk`. Note that you can have multiple targets of
-/// the same type.
+/// targets are: `email`, `zendesk`, `slack`, `webhook`. Note that you can have
+/// multiple targets of the same type.
+///
+/// Each target has its own `subject` and `template`. When a target doesn't set
+/// them, the `subject` and `template` at the top level of the alert are used
+/// instead, so targets can share templates and only override what differs:
+///
+/// ```yaml
+/// subject: "[Alert] Something is wrong"
+/// template: |
+///   <h1>Whoops</h1>
+///   <p>There are {{ rows | length }} rows.</p>
+/// send:
+///   - target: email
+///     addresses: [staff@job.com]
+///   - target: zendesk
+///     endpoint: https://example.zendesk.com/api/v2/requests
+///     requester: Tamanu alerts
+///     template: "There are {{ rows | length }} rows."
+/// ```
 ///
 /// ## Email
 ///
@@ -151,6 +290,66 @@ const DEFAULT_SUBJECT_TEMPLATE: &str = "[Tamanu Alert] {{ filename }} ({{ hostna
 ///         value: Test
 /// ```
 ///
+/// ## Slack
+///
+/// Posts to a Slack [incoming webhook](https://api.slack.com/messaging/webhooks).
+/// The rendered subject is the first line of the message, in bold, followed by
+/// the rendered template, which should be in Slack's `mrkdwn` format rather
+/// than HTML. The `channel`, `username`, `icon_emoji`, and `icon_url` fields
+/// are optional overrides of the webhook's defaults.
+///
+/// ```yaml
+/// send:
+///   - target: slack
+///     webhook: https://hooks.slack.com/services/T000/B000/XXXX
+///     channel: "#alerts"
+///     username: Tamanu alerts
+///     icon_emoji: ":rotating_light:"
+///     template: "There are {{ rows | length }} failed jobs."
+/// ```
+///
+/// ## Webhook
+///
+/// Sends an HTTP request to any URL. The rendered template is the body of the
+/// request, and must be a JSON object. With `format: json` (the default) it's
+/// sent as-is; with `format: form` it's sent form-encoded, in which case the
+/// values must be strings, numbers, or booleans. The `method` defaults to POST.
+///
+/// Header values are templates too, rendered with the same variables.
+/// A response with a non-2xx status is an error.
+///
+/// ```yaml
+/// send:
+///   - target: webhook
+///     url: https://example.com/api/alerts
+///     method: PUT
+///     headers:
+///       Authorization: Bearer abcdef
+///       X-Alert-Source: "{{ hostname }}"
+///     format: json
+///     template: |
+///       { "title": "{{ subject }}", "count": {{ rows | length }} }
+/// ```
+///
+/// ## Retries
+///
+/// If sending to a target fails because the service couldn't be reached, is
+/// rate-limiting, or is briefly unavailable (a 5xx error), it's tried again a
+/// few times with increasing delays before the failure is reported. Other
+/// failures, like a rejected request or a timeout after it was sent, aren't
+/// retried, so that the same email or ticket isn't sent twice. The number of attempts and the initial delay are set
+/// with `--send-attempts` and `--retry-delay`, and can be overridden per target
+/// (including in `_targets.yml`):
+///
+/// ```yaml
+/// send:
+///   - target: slack
+///     webhook: https://hooks.slack.com/services/T000/B000/XXXX
+///     retry:
+///       attempts: 5
+///       delay: 10s
+/// ```
+///
 /// ## External targets
 ///
 /// It can be tedious to specify and update the same addresses in many different
@@ -185,6 +384,24 @@ const DEFAULT_SUBJECT_TEMPLATE: &str = "[Tamanu Alert] {{ filename }} ({{ hostna
 ///     template: |
 ///       <h1>Whoops</h1>
 /// ```
+///
+/// An external target can be restricted to alerts of some severities only, so
+/// that e.g. critical alerts page someone while others only send an email.
+/// Without `severities`, a target receives alerts of any severity:
+///
+/// ```yaml
+/// targets:
+///   - id: email-staff
+///     target: email
+///     addresses:
+///       - staff@job.com
+///   - id: oncall-pager
+///     target: webhook
+///     url: https://events.example.com/page
+///     severities: [critical]
+/// ```
+///
+/// Alerts can then list both targets, and only send to those that match.
 #[cfg_attr(docsrs, doc("\n\n**Command**: `bestool tamanu alerts`"))]
 #[derive(Debug, Clone, Parser)]
 #[clap(verbatim_doc_comment)]
@@ -206,15 +423,88 @@ pub struct AlertsArgs {
 	/// How far back to look for alerts.
 	///
 	/// This is a duration string, e.g. `1d` for one day, `1h` for one hour, etc. It should match
-	/// the task scheduling / cron interval for this command.
+	/// the task scheduling / cron interval for this command. It's the default for alerts which
+	/// don't set their own `interval`.
 	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--interval DURATION`"))]
 	#[arg(long)]
 	pub interval: humantime::Duration,
 
+	/// The shortest interval that alerts can run at.
+	///
+	/// Alerts with a shorter interval (including from `--interval`) are clamped up to this, with
+	/// a warning.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--min-interval DURATION`"))]
+	#[arg(long)]
+	pub min_interval: Option<humantime::Duration>,
+
+	/// Spread the alerts' queries over this long, instead of running them all at once.
+	///
+	/// Each alert gets its own offset within this window, which is derived from its file path so
+	/// that it's the same on every run, and waits until then to run. This smooths the load on the
+	/// database when there are many alerts, or many servers running them on the same schedule. It
+	/// must be shorter than `--interval`. Dry runs don't wait.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--jitter DURATION`"))]
+	#[arg(long)]
+	pub jitter: Option<humantime::Duration>,
+
 	/// Don't actually send alerts, just print them to stdout.
 	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--dry-run`"))]
 	#[arg(long)]
 	pub dry_run: bool,
+
+	/// Output format for `--dry-run`.
+	///
+	/// With `json`, a JSON document is printed on its own line for each alert, with whether it
+	/// would fire, how many rows its query returned (for SQL sources), and for each send target
+	/// where it would go and whether its templates rendered. This is useful to check alerts in CI.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--dry-run-format text|json`"))]
+	#[arg(long, value_enum, default_value_t, requires = "dry_run")]
+	pub dry_run_format: DryRunFormat,
+
+	/// Write the process ID to this file, and hold an exclusive lock on it while running.
+	///
+	/// If another instance of this command already holds the lock on the same file, this exits
+	/// with an error instead of executing (and sending) the alerts a second time. Use this when
+	/// runs might overlap, e.g. with a short cron interval or slow queries.
+	///
+	/// The lock is released when the process exits, even if it crashes, so the file is left in place
+	/// and never needs to be removed by hand.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--pid-file PATH`"))]
+	#[arg(long)]
+	pub pid_file: Option<PathBuf>,
+
+	/// File to keep track of when alerts were last run and sent, for intervals and cooldowns.
+	///
+	/// It's created if it doesn't exist. Without it, cooldowns have no effect, and alerts with
+	/// their own interval run every time.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--state-file PATH`"))]
+	#[arg(long)]
+	pub state_file: Option<PathBuf>,
+
+	/// How many times to try sending to each target before giving up.
+	///
+	/// This includes the first attempt, so `1` disables retries.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--send-attempts N`"))]
+	#[arg(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..))]
+	pub send_attempts: u32,
+
+	/// How long to wait before retrying a failed send.
+	///
+	/// This doubles after each further failure, with some random jitter.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--retry-delay DURATION`"))]
+	#[arg(long, default_value = "5s")]
+	pub retry_delay: humantime::Duration,
+}
+
+/// Output format for `--dry-run`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DryRunFormat {
+	/// Print alerts as they would be sent.
+	#[default]
+	Text,
+
+	/// Print a JSON report per alert.
+	Json,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -253,17 +543,51 @@ struct AlertDefinition {
 	enabled: bool,
 	#[serde(skip)]
 	interval: Duration,
+	#[serde(
+		default,
+		rename = "interval",
+		deserialize_with = "deserialize_duration"
+	)]
+	own_interval: Option<Duration>,
 	#[serde(default)]
 	send: Vec<SendTarget>,
 
 	#[serde(flatten)]
 	source: TicketSource,
-
-	// legacy email-only fields
 	#[serde(default)]
-	recipients: Vec<String>,
+	fire_when_rows: RowsThreshold,
+	#[serde(default)]
+	severity: Severity,
+
+	#[serde(default, deserialize_with = "deserialize_duration")]
+	cooldown: Option<Duration>,
+	dedup_key: Option<String>,
+
+	// defaults for targets which don't specify their own
 	subject: Option<String>,
 	template: Option<String>,
+
+	// legacy email-only field
+	#[serde(default)]
+	recipients: Vec<String>,
+}
+
+/// How important an alert is.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Severity {
+	Info,
+	#[default]
+	Warning,
+	Critical,
+}
+
+fn deserialize_duration<'de, D: serde::Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+	<Option<String> as serde::Deserialize>::deserialize(deserializer)?
+		.map(|cooldown| humantime::parse_duration(&cooldown).map_err(serde::de::Error::custom))
+		.transpose()
 }
 
 #[derive(serde::Deserialize, Debug, Default)]
@@ -281,24 +605,130 @@ enum TicketSource {
 	None,
 }
 
+/// Condition on the number of rows returned by a SQL source for the alert to fire.
+///
+/// Parsed from a comparison like `>= 5` or `== 0`, or a bare number as a shorthand for `>=`.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "RowsThresholdSpec")]
+struct RowsThreshold {
+	op: RowsComparison,
+	count: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RowsComparison {
+	Greater,
+	GreaterOrEqual,
+	Equal,
+	NotEqual,
+	LessOrEqual,
+	Less,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum RowsThresholdSpec {
+	Count(usize),
+	Comparison(String),
+}
+
+impl Default for RowsThreshold {
+	fn default() -> Self {
+		Self {
+			op: RowsComparison::GreaterOrEqual,
+			count: 1,
+		}
+	}
+}
+
+impl TryFrom<RowsThresholdSpec> for RowsThreshold {
+	type Error = String;
+
+	fn try_from(spec: RowsThresholdSpec) -> Result<Self, Self::Error> {
+		let spec = match spec {
+			RowsThresholdSpec::Count(count) => {
+				return Ok(Self {
+					op: RowsComparison::GreaterOrEqual,
+					count,
+				})
+			}
+			RowsThresholdSpec::Comparison(spec) => spec,
+		};
+
+		let spec = spec.trim();
+		let (op, count) = [
+			(">=", RowsComparison::GreaterOrEqual),
+			("<=", RowsComparison::LessOrEqual),
+			("==", RowsComparison::Equal),
+			("!=", RowsComparison::NotEqual),
+			(">", RowsComparison::Greater),
+			("<", RowsComparison::Less),
+		]
+		.into_iter()
+		.find_map(|(prefix, op)| spec.strip_prefix(prefix).map(|count| (op, count)))
+		.unwrap_or((RowsComparison::GreaterOrEqual, spec));
+
+		let count = count
+			.trim()
+			.parse()
+			.map_err(|_| format!("invalid row threshold {spec:?}, expected e.g. \">= 5\""))?;
+		Ok(Self { op, count })
+	}
+}
+
+impl RowsThreshold {
+	fn matches(self, rows: usize) -> bool {
+		match self.op {
+			RowsComparison::Greater => rows > self.count,
+			RowsComparison::GreaterOrEqual => rows >= self.count,
+			RowsComparison::Equal => rows == self.count,
+			RowsComparison::NotEqual => rows != self.count,
+			RowsComparison::LessOrEqual => rows <= self.count,
+			RowsComparison::Less => rows < self.count,
+		}
+	}
+}
+
 #[derive(serde::Deserialize, Debug)]
 #[serde(rename_all = "snake_case", tag = "target")]
 enum SendTarget {
 	Email {
 		subject: Option<String>,
-		template: String,
+		template: Option<String>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
 		#[serde(flatten)]
 		conn: TargetEmail,
 	},
 	Zendesk {
 		subject: Option<String>,
-		template: String,
+		template: Option<String>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
 		#[serde(flatten)]
 		conn: TargetZendesk,
 	},
+	Slack {
+		subject: Option<String>,
+		template: Option<String>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
+		#[serde(flatten)]
+		conn: TargetSlack,
+	},
+	Webhook {
+		subject: Option<String>,
+		template: Option<String>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
+		#[serde(flatten)]
+		conn: TargetWebhook,
+	},
 	External {
 		subject: Option<String>,
-		template: String,
+		template: Option<String>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
 		id: String,
 		#[serde(default, skip)]
 		resolved: Option<ExternalTarget>,
@@ -307,13 +737,60 @@ enum SendTarget {
 
 impl SendTarget {
 	fn resolve_external(&mut self, external_targets: &HashMap<String, ExternalTarget>) {
-		match self {
-			Self::External { id, resolved, .. } => {
-				if let Some(target) = external_targets.get(id) {
-					*resolved = Some(target.clone());
-				}
+		if let Self::External { id, resolved, .. } = self {
+			if let Some(target) = external_targets.get(id) {
+				*resolved = Some(target.clone());
 			}
-			_ => {}
+		}
+	}
+
+	/// The retry policy for this target, from its own overrides and those of its external target.
+	fn retry_policy(&self, policy: RetryPolicy) -> RetryPolicy {
+		let (Self::Email { retry, .. }
+		| Self::Zendesk { retry, .. }
+		| Self::Slack { retry, .. }
+		| Self::Webhook { retry, .. }
+		| Self::External { retry, .. }) = self;
+
+		let external = match self {
+			Self::External {
+				resolved:
+					Some(
+						ExternalTarget::Email { retry, .. }
+						| ExternalTarget::Zendesk { retry, .. }
+						| ExternalTarget::Slack { retry, .. }
+						| ExternalTarget::Webhook { retry, .. },
+					),
+				..
+			} => retry.as_ref(),
+			_ => None,
+		};
+
+		policy.with(external).with(retry.as_ref())
+	}
+
+	fn default_templates(&mut self, default_subject: Option<&str>, default_template: Option<&str>) {
+		let (Self::Email {
+			subject, template, ..
+		}
+		| Self::Zendesk {
+			subject, template, ..
+		}
+		| Self::Slack {
+			subject, template, ..
+		}
+		| Self::Webhook {
+			subject, template, ..
+		}
+		| Self::External {
+			subject, template, ..
+		}) = self;
+
+		if subject.is_none() {
+			*subject = default_subject.map(ToOwned::to_owned);
+		}
+		if template.is_none() {
+			*template = default_template.map(ToOwned::to_owned);
 		}
 	}
 }
@@ -324,7 +801,7 @@ struct AlertTargets {
 }
 
 impl AlertTargets {
-	fn to_map(self) -> HashMap<String, ExternalTarget> {
+	fn into_map(self) -> HashMap<String, ExternalTarget> {
 		self.targets
 			.into_iter()
 			.map(|target| (target.id().into(), target))
@@ -337,14 +814,40 @@ impl AlertTargets {
 enum ExternalTarget {
 	Email {
 		id: String,
+		#[serde(default)]
+		severities: Vec<Severity>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
 		#[serde(flatten)]
 		conn: TargetEmail,
 	},
 	Zendesk {
 		id: String,
+		#[serde(default)]
+		severities: Vec<Severity>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
 		#[serde(flatten)]
 		conn: TargetZendesk,
 	},
+	Slack {
+		id: String,
+		#[serde(default)]
+		severities: Vec<Severity>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
+		#[serde(flatten)]
+		conn: TargetSlack,
+	},
+	Webhook {
+		id: String,
+		#[serde(default)]
+		severities: Vec<Severity>,
+		#[serde(default)]
+		retry: Option<RetryOverride>,
+		#[serde(flatten)]
+		conn: TargetWebhook,
+	},
 }
 
 impl ExternalTarget {
@@ -352,8 +855,19 @@ impl ExternalTarget {
 		match self {
 			Self::Email { id, .. } => id,
 			Self::Zendesk { id, .. } => id,
+			Self::Slack { id, .. } => id,
+			Self::Webhook { id, .. } => id,
 		}
 	}
+
+	/// Whether alerts of this severity should be sent to this target.
+	fn accepts(&self, severity: Severity) -> bool {
+		let (Self::Email { severities, .. }
+		| Self::Zendesk { severities, .. }
+		| Self::Slack { severities, .. }
+		| Self::Webhook { severities, .. }) = self;
+		severities.is_empty() || severities.contains(&severity)
+	}
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -375,6 +889,142 @@ struct TargetZendesk {
 	custom_fields: Vec<ZendeskCustomField>,
 }
 
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+struct TargetSlack {
+	webhook: Url,
+	channel: Option<String>,
+	username: Option<String>,
+	icon_emoji: Option<String>,
+	icon_url: Option<Url>,
+}
+
+impl TargetSlack {
+	fn payload(&self, subject: &str, body: &str) -> serde_json::Value {
+		let mut payload = json!({ "text": format!("*{subject}*\n{body}") });
+		for (key, value) in [
+			("channel", self.channel.as_deref()),
+			("username", self.username.as_deref()),
+			("icon_emoji", self.icon_emoji.as_deref()),
+			("icon_url", self.icon_url.as_ref().map(Url::as_str)),
+		] {
+			if let Some(value) = value {
+				payload[key] = value.into();
+			}
+		}
+		payload
+	}
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+struct TargetWebhook {
+	url: Url,
+	#[serde(
+		default = "default_webhook_method",
+		deserialize_with = "deserialize_method"
+	)]
+	method: reqwest::Method,
+	#[serde(default)]
+	headers: BTreeMap<String, String>,
+	#[serde(default)]
+	format: WebhookFormat,
+}
+
+fn default_webhook_method() -> reqwest::Method {
+	reqwest::Method::POST
+}
+
+fn deserialize_method<'de, D: serde::Deserializer<'de>>(
+	deserializer: D,
+) -> Result<reqwest::Method, D::Error> {
+	let method = <String as serde::Deserialize>::deserialize(deserializer)?;
+	reqwest::Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+		.map_err(serde::de::Error::custom)
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum WebhookFormat {
+	#[default]
+	Json,
+	Form,
+}
+
+/// A webhook request body, encoded from the rendered template.
+#[derive(Debug, PartialEq)]
+enum WebhookBody {
+	Json(serde_json::Value),
+	Form(Vec<(String, String)>),
+}
+
+impl WebhookFormat {
+	fn encode(self, body: &str) -> Result<WebhookBody> {
+		let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(body)
+			.into_diagnostic()
+			.wrap_err("webhook body must be a JSON object")?;
+
+		Ok(match self {
+			Self::Json => WebhookBody::Json(object.into()),
+			Self::Form => WebhookBody::Form(
+				object
+					.into_iter()
+					.map(|(key, value)| {
+						let value = match value {
+							serde_json::Value::String(value) => value,
+							serde_json::Value::Null => String::new(),
+							value @ (serde_json::Value::Bool(_) | serde_json::Value::Number(_)) => {
+								value.to_string()
+							}
+							_ => {
+								return Err(miette!(
+									"form field {key:?} must be a string, number, or boolean"
+								))
+							}
+						};
+						Ok((key, value))
+					})
+					.collect::<Result<_>>()?,
+			),
+		})
+	}
+}
+
+impl TargetWebhook {
+	/// The request to send, with rendered headers and an encoded body.
+	fn request(
+		&self,
+		client: &reqwest::Client,
+		headers: &[(String, String)],
+		body: &WebhookBody,
+	) -> reqwest::RequestBuilder {
+		let mut req_builder = client.request(self.method.clone(), self.url.clone());
+		for (name, value) in headers {
+			req_builder = req_builder.header(name, value);
+		}
+		match body {
+			WebhookBody::Json(body) => req_builder.json(body),
+			WebhookBody::Form(fields) => req_builder.form(fields),
+		}
+	}
+}
+
+fn render_headers(
+	tera: &Tera,
+	conn: &TargetWebhook,
+	context: &TeraCtx,
+) -> Result<Vec<(String, String)>> {
+	conn.headers
+		.keys()
+		.map(|name| {
+			tera.render(&format!("header:{name}"), context)
+				.map_err(templates::diagnose)
+				.wrap_err(format!("rendering {name} header template"))
+				.map(|value| (name.clone(), value))
+		})
+		.collect()
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 #[serde(untagged, deny_unknown_fields)]
 enum ZendeskMethod {
@@ -401,72 +1051,451 @@ impl AlertDefinition {
 	fn normalise(mut self, external_targets: &HashMap<String, ExternalTarget>) -> Self {
 		if !self.recipients.is_empty() {
 			self.send.push(SendTarget::Email {
-				subject: self.subject,
-				template: self.template.unwrap_or_default(),
+				subject: None,
+				template: Some(self.template.clone().unwrap_or_default()),
+				retry: None,
 				conn: TargetEmail {
-					addresses: self.recipients,
+					addresses: std::mem::take(&mut self.recipients),
 				},
 			});
-			self.recipients = vec![];
-			self.subject = None;
-			self.template = None;
 		}
 
 		for target in &mut self.send {
 			target.resolve_external(external_targets);
+			target.default_templates(self.subject.as_deref(), self.template.as_deref());
 		}
 
+		let severity = self.severity;
+		self.send.retain(|target| match target {
+			SendTarget::External {
+				id,
+				resolved: Some(resolved),
+				..
+			} if !resolved.accepts(severity) => {
+				debug!(
+					?id,
+					?severity,
+					"external target doesn't take this severity, skipping"
+				);
+				false
+			}
+			_ => true,
+		});
+
 		self
 	}
 }
 
-struct InternalContext {
-	pg_client: tokio_postgres::Client,
-	http_client: reqwest::Client,
+/// Template partials, loaded from `_templates` folders, by name.
+#[derive(Debug, Default)]
+struct Partials {
+	templates: HashMap<String, String>,
+
+	/// Partials which have errors, and were left out of `templates`.
+	broken: HashSet<String>,
 }
 
-pub async fn run(ctx: Context<TamanuArgs, AlertsArgs>) -> Result<()> {
-	let (_, root) = find_tamanu(&ctx.args_top)?;
-	let kind = find_package(&root);
-	let config_value = load_config(&root, kind.package_name())?;
-	let config: TamanuConfig = serde_json::from_value(config_value)
-		.into_diagnostic()
-		.wrap_err("parsing of Tamanu config failed")?;
-	debug!(?config, "parsed Tamanu config");
+impl Partials {
+	/// Names of templates which are provided by the alert itself.
+	const RESERVED: [&'static str; 3] = ["subject", "alert.html", "requester"];
 
-	let mut alerts = Vec::<AlertDefinition>::new();
-	let mut external_targets = HashMap::new();
-	for dir in ctx.args_sub.dir {
-		let external_targets_path = dir.join("_targets.yml");
-		if let Some(target) = std::fs::read_to_string(&external_targets_path)
-			.ok()
-			.and_then(|content| {
-				debug!(path=?external_targets_path, "parsing external targets");
-				serde_yml::from_str::<AlertTargets>(&content)
-					.map_err(
-						|err| warn!(path=?external_targets_path, "_targets.yml has errors! {err}"),
-					)
-					.ok()
-			}) {
-			external_targets.extend(target.to_map().into_iter());
+	/// Load the partials in a folder.
+	///
+	/// Files which can't be loaded are reported and left out.
+	fn load(&mut self, dir: &Path) {
+		for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+			if !entry.file_type().is_file() {
+				continue;
+			}
+
+			let file = entry.path();
+			let name = file
+				.strip_prefix(dir)
+				.unwrap_or(file)
+				.with_extension("")
+				.components()
+				.map(|c| c.as_os_str().to_string_lossy())
+				.collect::<Vec<_>>()
+				.join("/");
+			if Self::RESERVED.contains(&name.as_str()) {
+				error!(
+					?file,
+					"the template partial name {name:?} is reserved, skipping"
+				);
+				continue;
+			}
+
+			debug!(?file, %name, "loading template partial");
+			match std::fs::read_to_string(file) {
+				Ok(content) => {
+					self.templates.insert(name, content);
+				}
+				Err(err) => {
+					error!(?file, "can't read template partial, skipping: {err}");
+					self.broken.insert(name);
+				}
+			}
 		}
+	}
 
-		alerts.extend(
+	/// Report and set aside partials with errors, so only the alerts which use them are skipped.
+	fn remove_broken(&mut self) {
+		while let Some((names, err)) = self.find_error() {
+			error!("{err:?}");
+			for name in names {
+				self.templates.remove(&name);
+				self.broken.insert(name);
+			}
+		}
+	}
+
+	/// Find a partial which doesn't parse or includes one that doesn't exist, or a cycle.
+	///
+	/// Returns the names of the partials at fault and the error.
+	fn find_error(&self) -> Option<(Vec<String>, miette::Report)> {
+		for (name, template) in &self.templates {
+			if let Err(err) = tera::Template::new(name, None, template)
+				.map_err(templates::diagnose)
+				.wrap_err(format!("compiling template partial {name:?}"))
+				.and_then(|_| self.check_includes(name, template))
+			{
+				return Some((vec![name.clone()], err));
+			}
+		}
+
+		fn visit<'a>(
+			partials: &'a Partials,
+			name: &'a str,
+			path: &mut Vec<&'a str>,
+			done: &mut Vec<&'a str>,
+		) -> Option<Vec<&'a str>> {
+			if done.contains(&name) {
+				return None;
+			}
+			if let Some(start) = path.iter().position(|n| *n == name) {
+				let mut cycle = path[start..].to_vec();
+				cycle.push(name);
+				return Some(cycle);
+			}
+
+			path.push(name);
+			if let Some(template) = partials.templates.get(name) {
+				for (include, _) in template_includes(template) {
+					if let Some(cycle) = visit(partials, include, path, done) {
+						return Some(cycle);
+					}
+				}
+			}
+			path.pop();
+			done.push(name);
+			None
+		}
+
+		let mut done = Vec::new();
+		self.templates.keys().find_map(|name| {
+			let cycle = visit(self, name, &mut Vec::new(), &mut done)?;
+			let err = miette!(
+				"template partials include each other in a cycle: {}",
+				cycle.join(" -> ")
+			);
+			// the first name is repeated at the end
+			Some((
+				cycle[1..].iter().map(|name| name.to_string()).collect(),
+				err,
+			))
+		})
+	}
+
+	/// Check that a template only includes partials that exist (or other templates of the alert).
+	fn check_includes(&self, name: &str, template: &str) -> Result<()> {
+		for (include, ignore_missing) in template_includes(template) {
+			if ignore_missing
+				|| self.templates.contains_key(include)
+				|| Self::RESERVED.contains(&include)
+			{
+				continue;
+			}
+
+			return Err(if self.broken.contains(include) {
+				miette!(
+					help = "the errors in the partial were reported when it was loaded",
+					"template {name:?} includes {include:?}, which has errors"
+				)
+			} else {
+				miette!(
+					help = "partials are loaded from `_templates` folders in the alert --dir(s)",
+					"template {name:?} includes {include:?}, which doesn't exist"
+				)
+			});
+		}
+
+		Ok(())
+	}
+}
+
+/// Find the names of templates included by a template, and whether missing ones are ignored.
+///
+/// This is a lightweight scan for `{% include "name" %}` tags (including the list form and
+/// `ignore missing`), rather than a full parse.
+fn template_includes(template: &str) -> Vec<(&str, bool)> {
+	let mut includes = Vec::new();
+	let mut rest = template;
+	while let Some(start) = rest.find("{%") {
+		rest = &rest[start + 2..];
+		let Some(end) = rest.find("%}") else {
+			break;
+		};
+		let tag = rest[..end].trim_matches(|c: char| c == '-' || c.is_whitespace());
+		rest = &rest[end + 2..];
+
+		let Some(args) = tag.strip_prefix("include") else {
+			continue;
+		};
+		if !args.starts_with(|c: char| c.is_whitespace() || c == '"' || c == '[') {
+			continue;
+		}
+
+		let ignore_missing = args.trim_end().ends_with("ignore missing");
+		includes.extend(
+			args.split(['"', '\''])
+				.skip(1)
+				.step_by(2)
+				.map(|name| (name, ignore_missing)),
+		);
+	}
+	includes
+}
+
+struct InternalContext {
+	pg_client: tokio_postgres::Client,
+	http_client: reqwest::Client,
+	partials: Partials,
+	retry: RetryPolicy,
+}
+
+/// An exclusively-locked file containing the process ID.
+///
+/// The file isn't removed on drop: another instance could open it between the removal and the
+/// unlock, and lock a file that's no longer at the path, letting a third one in. It's emptied
+/// instead, and the lock alone decides whether an instance is running.
+#[derive(Debug)]
+struct PidFile {
+	path: PathBuf,
+	file: File,
+}
+
+impl PidFile {
+	fn acquire(path: &Path) -> Result<Self> {
+		// don't truncate on open, as that would erase the PID of a running instance
+		let mut file = OpenOptions::new()
+			.create(true)
+			.truncate(false)
+			.write(true)
+			.open(path)
+			.into_diagnostic()
+			.wrap_err(format!("opening pid file {path:?}"))?;
+
+		if file.try_lock_exclusive().is_err() {
+			let pid = std::fs::read_to_string(path).unwrap_or_default();
+			return Err(miette!(
+				help = "wait for the other instance to finish; the lock is released when it exits",
+				"another instance is already running (pid file {path:?} is locked{})",
+				if pid.trim().is_empty() {
+					String::new()
+				} else {
+					format!(" by pid {}", pid.trim())
+				}
+			));
+		}
+
+		file.set_len(0)
+			.into_diagnostic()
+			.wrap_err("truncating pid file")?;
+		writeln!(file, "{}", process::id())
+			.into_diagnostic()
+			.wrap_err("writing pid file")?;
+		debug!(?path, "acquired pid file");
+
+		Ok(Self {
+			path: path.into(),
+			file,
+		})
+	}
+}
+
+impl Drop for PidFile {
+	fn drop(&mut self) {
+		// clear the pid while still holding the lock, so no other instance's pid is erased
+		if let Err(err) = self.file.set_len(0) {
+			warn!(path=?self.path, "failed to clear pid file: {err}");
+		}
+		let _ = FileExt::unlock(&self.file);
+	}
+}
+
+/// What's kept in the state file between runs.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq)]
+struct State {
+	/// When each alert was last sent, and with which dedup key, by alert file and target index.
+	#[serde(default)]
+	sent: HashMap<PathBuf, HashMap<usize, LastSent>>,
+
+	/// When each alert last ran, by alert file.
+	#[serde(default)]
+	last_run: HashMap<PathBuf, DateTime<Utc>>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+struct LastSent {
+	key: String,
+	at: DateTime<Utc>,
+}
+
+impl State {
+	fn load(path: &Path) -> Self {
+		match std::fs::read_to_string(path) {
+			Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+				warn!(?path, "state file is invalid, starting afresh: {err}");
+				Self::default()
+			}),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+			Err(err) => {
+				warn!(?path, "can't read state file, starting afresh: {err}");
+				Self::default()
+			}
+		}
+	}
+
+	fn save(&self, path: &Path) -> Result<()> {
+		// write alongside and rename, so an interrupted write doesn't lose the state
+		let mut temporary = path.as_os_str().to_owned();
+		temporary.push(".new");
+		std::fs::write(&temporary, serde_json::to_string(self).into_diagnostic()?)
+			.into_diagnostic()
+			.wrap_err(format!("writing state file {temporary:?}"))?;
+		std::fs::rename(&temporary, path)
+			.into_diagnostic()
+			.wrap_err(format!("replacing state file {path:?}"))
+	}
+
+	/// Whether sending an alert firing now with this key to a target is a repeat within its cooldown.
+	fn is_cooling_down(
+		&self,
+		file: &Path,
+		target: usize,
+		key: &str,
+		cooldown: Duration,
+		now: DateTime<Utc>,
+	) -> bool {
+		self.sent
+			.get(file)
+			.and_then(|targets| targets.get(&target))
+			.is_some_and(|last| {
+				last.key == key && (now - last.at).to_std().unwrap_or_default() < cooldown
+			})
+	}
+
+	fn record(&mut self, file: &Path, target: usize, key: String, now: DateTime<Utc>) {
+		self.sent
+			.entry(file.into())
+			.or_default()
+			.insert(target, LastSent { key, at: now });
+	}
+
+	/// Whether an alert's interval has passed since it last ran, give or take the tolerance.
+	fn is_due(
+		&self,
+		file: &Path,
+		interval: Duration,
+		tolerance: Duration,
+		now: DateTime<Utc>,
+	) -> bool {
+		self.last_run.get(file).map_or(true, |last| {
+			(now - *last).to_std().unwrap_or_default() + tolerance >= interval
+		})
+	}
+}
+
+/// Resolve an alert's interval from its own, the default from `--interval`, and the floor from
+/// `--min-interval`.
+fn resolve_interval(
+	file: &Path,
+	own: Option<Duration>,
+	default: Duration,
+	min: Option<Duration>,
+) -> Duration {
+	let interval = own.unwrap_or(default);
+	match min {
+		Some(min) if interval < min => {
+			warn!(
+				?file,
+				interval = %humantime::format_duration(interval),
+				min = %humantime::format_duration(min),
+				"alert interval is shorter than --min-interval, clamping it"
+			);
+			min
+		}
+		_ => interval,
+	}
+}
+
+pub async fn run(ctx: Context<TamanuArgs, AlertsArgs>) -> Result<()> {
+	let _pid_file = ctx
+		.args_sub
+		.pid_file
+		.as_deref()
+		.map(PidFile::acquire)
+		.transpose()?;
+
+	let (_, root) = find_tamanu(&ctx.args_top)?;
+	let kind = find_package(&root);
+	let config_value = load_config(&root, kind.package_name())?;
+	let config: TamanuConfig = serde_json::from_value(config_value)
+		.into_diagnostic()
+		.wrap_err("parsing of Tamanu config failed")?;
+	debug!(?config, "parsed Tamanu config");
+
+	let mut alerts = Vec::<AlertDefinition>::new();
+	let mut external_targets = HashMap::new();
+	let mut partials = Partials::default();
+	for dir in ctx.args_sub.dir {
+		let partials_path = dir.join("_templates");
+		if partials_path.is_dir() {
+			partials.load(&partials_path);
+		}
+
+		let external_targets_path = dir.join("_targets.yml");
+		if let Some(target) = std::fs::read_to_string(&external_targets_path)
+			.ok()
+			.and_then(|content| {
+				debug!(path=?external_targets_path, "parsing external targets");
+				serde_yml::from_str::<AlertTargets>(&content)
+					.map_err(
+						|err| warn!(path=?external_targets_path, "_targets.yml has errors! {err}"),
+					)
+					.ok()
+			}) {
+			external_targets.extend(target.into_map());
+		}
+
+		alerts.extend(
 			WalkDir::new(dir)
+				.sort_by_file_name()
 				.into_iter()
 				.filter_map(|e| e.ok())
 				.filter(|e| e.file_type().is_file())
 				.map(|entry| {
 					let file = entry.path();
 
-					if !file
-						.extension()
-						.map_or(false, |e| e == "yaml" || e == "yml")
-					{
+					if !file.extension().is_some_and(|e| e == "yaml" || e == "yml") {
 						return Ok(None);
 					}
 
-					if file.file_stem().map_or(false, |n| n == "_targets") {
+					if file.file_stem().is_some_and(|n| n == "_targets") {
+						return Ok(None);
+					}
+
+					if file.starts_with(&partials_path) {
 						return Ok(None);
 					}
 
@@ -479,7 +1508,12 @@ pub async fn run(ctx: Context<TamanuArgs, AlertsArgs>) -> Result<()> {
 						.wrap_err(format!("{file:?}"))?;
 
 					alert.file = file.to_path_buf();
-					alert.interval = ctx.args_sub.interval.into();
+					alert.interval = resolve_interval(
+						file,
+						alert.own_interval,
+						ctx.args_sub.interval.into(),
+						ctx.args_sub.min_interval.map(Into::into),
+					);
 					debug!(?alert, "parsed alert file");
 					Ok(if alert.enabled { Some(alert) } else { None })
 				})
@@ -502,13 +1536,36 @@ pub async fn run(ctx: Context<TamanuArgs, AlertsArgs>) -> Result<()> {
 		debug!(count=%external_targets.len(), "found some external targets");
 	}
 
+	if !partials.templates.is_empty() {
+		debug!(count=%partials.templates.len(), "found some template partials");
+		partials.remove_broken();
+	}
+
 	for alert in &mut alerts {
 		*alert = std::mem::take(alert).normalise(&external_targets);
 	}
+	alerts.retain(|alert| {
+		alert
+			.send
+			.iter()
+			.try_for_each(|target| load_templates(target, &partials).map(drop))
+			.map_err(|err| error!("{:?}", err.wrap_err(format!("{:?}", alert.file))))
+			.is_ok()
+	});
 	debug!(count=%alerts.len(), "found some alerts");
 
+	let jitter = ctx.args_sub.jitter.map_or(Duration::ZERO, Into::into);
+	if jitter >= *ctx.args_sub.interval {
+		bail!("--jitter must be shorter than --interval");
+	}
+	let mut alerts = alerts
+		.into_iter()
+		.map(|alert| (jitter_offset(&alert.file, jitter), alert))
+		.collect::<Vec<_>>();
+	alerts.sort_by_key(|(offset, _)| *offset);
+
 	let mut pg_config = tokio_postgres::Config::default();
-	pg_config.application_name(&format!(
+	pg_config.application_name(format!(
 		"{}/{} (tamanu alerts)",
 		env!("CARGO_PKG_NAME"),
 		env!("CARGO_PKG_VERSION")
@@ -535,24 +1592,83 @@ pub async fn run(ctx: Context<TamanuArgs, AlertsArgs>) -> Result<()> {
 	let internal_ctx = InternalContext {
 		pg_client: client,
 		http_client: reqwest::Client::new(),
+		partials,
+		retry: RetryPolicy {
+			attempts: ctx.args_sub.send_attempts,
+			delay: ctx.args_sub.retry_delay.into(),
+		},
 	};
 
-	for alert in alerts {
-		if let Err(err) =
-			execute_alert(&internal_ctx, &config.mailgun, &alert, ctx.args_sub.dry_run)
-				.await
-				.wrap_err(format!("while executing alert: {}", alert.file.display()))
+	let state_file = ctx.args_sub.state_file.as_deref();
+	let mut state = state_file.map(State::load).unwrap_or_default();
+	if state_file.is_none() && alerts.iter().any(|(_, alert)| alert.cooldown.is_some()) {
+		warn!("some alerts have a cooldown, but it has no effect without --state-file");
+	}
+	if state_file.is_none()
+		&& alerts
+			.iter()
+			.any(|(_, alert)| alert.interval > *ctx.args_sub.interval)
+	{
+		warn!("some alerts have a longer interval, but they run every time without --state-file");
+	}
+	// leeway for the scheduler not running us at exactly the same time every interval
+	let tolerance = *ctx.args_sub.interval / 2;
+
+	let dry_run = ctx.args_sub.dry_run.then_some(ctx.args_sub.dry_run_format);
+	let started = Instant::now();
+	for (offset, alert) in alerts {
+		let now = Utc::now();
+		if !state.is_due(&alert.file, alert.interval, tolerance, now) {
+			info!(?alert.file, "alert isn't due to run yet, skipping");
+			continue;
+		}
+
+		if let Some(wait) = offset
+			.checked_sub(started.elapsed())
+			.filter(|_| dry_run.is_none())
+		{
+			debug!(?alert.file, ?wait, "waiting for the alert's jitter offset");
+			tokio::time::sleep(wait).await;
+		}
+
+		match execute_alert(&internal_ctx, &config.mailgun, &alert, dry_run, &mut state)
+			.await
+			.wrap_err(format!("while executing alert: {}", alert.file.display()))
 		{
-			eprintln!("{err:?}");
+			Ok(()) => {
+				state.last_run.insert(alert.file, now);
+			}
+			Err(err) => eprintln!("{err:?}"),
 		}
 	}
 
+	if let Some(path) = state_file.filter(|_| dry_run.is_none()) {
+		state.save(path)?;
+	}
+
 	Ok(())
 }
 
-#[instrument]
-fn load_templates(target: &SendTarget) -> Result<Tera> {
-	let mut tera = tera::Tera::default();
+/// When to run an alert within the `--jitter` window.
+///
+/// This is spread evenly over the window by a hash of the alert's file, so each alert keeps the same
+/// offset from one run to the next.
+fn jitter_offset(file: &Path, jitter: Duration) -> Duration {
+	if jitter.is_zero() {
+		return Duration::ZERO;
+	}
+
+	let mut hasher = DefaultHasher::new();
+	file.hash(&mut hasher);
+	jitter.mul_f64(hasher.finish() as f64 / u64::MAX as f64)
+}
+
+#[instrument(skip(partials))]
+fn load_templates(target: &SendTarget, partials: &Partials) -> Result<Tera> {
+	let mut tera = templates::tera();
+	tera.add_raw_templates(&partials.templates)
+		.into_diagnostic()
+		.wrap_err("compiling template partials")?;
 
 	match target {
 		SendTarget::Email {
@@ -561,16 +1677,29 @@ fn load_templates(target: &SendTarget) -> Result<Tera> {
 		| SendTarget::Zendesk {
 			subject, template, ..
 		}
+		| SendTarget::Slack {
+			subject, template, ..
+		}
+		| SendTarget::Webhook {
+			subject, template, ..
+		}
 		| SendTarget::External {
 			subject, template, ..
 		} => {
-			tera.add_raw_template(
-				"subject",
-				subject.as_deref().unwrap_or(DEFAULT_SUBJECT_TEMPLATE),
-			)
-			.into_diagnostic()
-			.wrap_err("compiling subject template")?;
-			tera.add_raw_template("alert.html", &template)
+			let subject = subject.as_deref().unwrap_or(DEFAULT_SUBJECT_TEMPLATE);
+			let template = template.as_deref().ok_or_else(|| {
+				miette!(
+					help = "set a `template` on the target, or at the top level of the alert",
+					"send target has no template"
+				)
+			})?;
+			partials.check_includes("subject", subject)?;
+			partials.check_includes("alert.html", template)?;
+
+			tera.add_raw_template("subject", subject)
+				.into_diagnostic()
+				.wrap_err("compiling subject template")?;
+			tera.add_raw_template("alert.html", template)
 				.into_diagnostic()
 				.wrap_err("compiling email template")?;
 		}
@@ -600,6 +1729,21 @@ fn load_templates(target: &SendTarget) -> Result<Tera> {
 			.into_diagnostic()
 			.wrap_err("compiling requester template")?;
 	}
+
+	if let SendTarget::Webhook { conn, .. }
+	| SendTarget::External {
+		resolved: Some(ExternalTarget::Webhook { conn, .. }),
+		..
+	} = target
+	{
+		for (name, value) in &conn.headers {
+			let template = format!("header:{name}");
+			partials.check_includes(&template, value)?;
+			tera.add_raw_template(&template, value)
+				.into_diagnostic()
+				.wrap_err(format!("compiling {name} header template"))?;
+		}
+	}
 	Ok(tera)
 }
 
@@ -622,21 +1766,26 @@ fn build_context(alert: &AlertDefinition, now: chrono::DateTime<chrono::Utc>) ->
 		&alert.file.file_name().unwrap().to_string_lossy(),
 	);
 	context.insert("now", &now.to_string());
+	context.insert("severity", &alert.severity);
 
 	context
 }
 
+/// Run an alert's source, and decide whether it fires.
+///
+/// Also returns how many rows an SQL source found, whether or not that was enough to fire.
 #[instrument(skip(client, alert, not_before, context))]
 async fn read_sources(
 	client: &tokio_postgres::Client,
 	alert: &AlertDefinition,
 	not_before: DateTime<Utc>,
 	context: &mut TeraCtx,
-) -> Result<ControlFlow<(), ()>> {
+) -> Result<(ControlFlow<()>, Option<usize>)> {
+	let mut row_count = None;
 	match &alert.source {
 		TicketSource::None => {
 			debug!(?alert.file, "no source, skipping");
-			return Ok(ControlFlow::Break(()));
+			return Ok((ControlFlow::Break(()), None));
 		}
 		TicketSource::Sql { sql } => {
 			let statement = client.prepare(sql).await.into_diagnostic()?;
@@ -650,10 +1799,11 @@ async fn read_sources(
 				.into_diagnostic()
 				.wrap_err("querying database")?;
 
-			if rows.is_empty() {
-				debug!(?alert.file, "no rows returned, skipping");
-				return Ok(ControlFlow::Break(()));
+			if !alert.fire_when_rows.matches(rows.len()) {
+				debug!(?alert.file, rows=%rows.len(), threshold=?alert.fire_when_rows, "row count doesn't meet the threshold, skipping");
+				return Ok((ControlFlow::Break(()), Some(rows.len())));
 			}
+			row_count = Some(rows.len());
 			info!(?alert.file, rows=%rows.len(), "alert triggered");
 
 			let context_rows = rows_to_value_map(&rows);
@@ -682,56 +1832,57 @@ async fn read_sources(
 			let Ok(res) = tokio::time::timeout(alert.interval, output_future).await else {
 				warn!(?alert.file, "the script timed out, skipping");
 				shell.kill().await.into_diagnostic()?;
-				return Ok(ControlFlow::Break(()));
+				return Ok((ControlFlow::Break(()), None));
 			};
 
 			let (status, output_size) = res.into_diagnostic().wrap_err("running the shell")?;
 
 			if status.success() {
 				debug!(?alert.file, "the script succeeded, skipping");
-				return Ok(ControlFlow::Break(()));
+				return Ok((ControlFlow::Break(()), None));
 			}
 			info!(?alert.file, ?status, ?output_size, "alert triggered");
 
 			context.insert("output", &String::from_utf8_lossy(&output));
 		}
 	}
-	Ok(ControlFlow::Continue(()))
+	Ok((ControlFlow::Continue(()), row_count))
 }
 
 #[instrument(skip(tera, context))]
 fn render_alert(tera: &Tera, context: &mut TeraCtx) -> Result<(String, String, Option<String>)> {
 	let subject = tera
-		.render("subject", &context)
-		.into_diagnostic()
+		.render("subject", context)
+		.map_err(templates::diagnose)
 		.wrap_err("rendering subject template")?;
 
 	context.insert("subject", &subject.to_string());
 
 	let body = tera
-		.render("alert.html", &context)
-		.into_diagnostic()
+		.render("alert.html", context)
+		.map_err(templates::diagnose)
 		.wrap_err("rendering email template")?;
 
 	let requester = tera
-		.render("requester", &context)
+		.render("requester", context)
 		.map(Some)
 		.or_else(|err| match err.kind {
 			tera::ErrorKind::TemplateNotFound(_) => Ok(None),
 			_ => Err(err),
 		})
-		.into_diagnostic()
+		.map_err(templates::diagnose)
 		.wrap_err("rendering requester template")?;
 
 	Ok((subject, body, requester))
 }
 
-#[instrument(skip(ctx, mailgun, alert))]
+#[instrument(skip(ctx, mailgun, alert, state))]
 async fn execute_alert(
 	ctx: &InternalContext,
 	mailgun: &TamanuMailgun,
 	alert: &AlertDefinition,
-	dry_run: bool,
+	dry_run: Option<DryRunFormat>,
+	state: &mut State,
 ) -> Result<()> {
 	info!(?alert.file, "executing alert");
 
@@ -740,16 +1891,43 @@ async fn execute_alert(
 	info!(?now, ?not_before, interval=?alert.interval, "date range for alert");
 
 	let mut tera_ctx = build_context(alert, now);
-	if read_sources(&ctx.pg_client, alert, not_before, &mut tera_ctx)
-		.await?
-		.is_break()
-	{
+	let sources = read_sources(&ctx.pg_client, alert, not_before, &mut tera_ctx).await;
+	if dry_run == Some(DryRunFormat::Json) {
+		println!(
+			"{}",
+			dry_run_report(alert, sources, &ctx.partials, &mut tera_ctx)
+		);
+		return Ok(());
+	}
+
+	if sources?.0.is_break() {
 		return Ok(());
 	}
 
-	for target in &alert.send {
-		let tera = load_templates(target)?;
+	let dedup_key = match &alert.dedup_key {
+		Some(template) => {
+			let mut tera = templates::tera();
+			tera.add_raw_template("dedup_key", template)
+				.into_diagnostic()
+				.wrap_err("compiling dedup key template")?;
+			tera.render("dedup_key", &tera_ctx)
+				.map_err(templates::diagnose)
+				.wrap_err("rendering dedup key template")?
+		}
+		None => String::new(),
+	};
+
+	for (index, target) in alert.send.iter().enumerate() {
+		if let Some(cooldown) = alert.cooldown {
+			if state.is_cooling_down(&alert.file, index, &dedup_key, cooldown, now) {
+				info!(?alert.file, target=index, ?cooldown, "alert was already sent to this target within its cooldown, skipping");
+				continue;
+			}
+		}
+
+		let tera = load_templates(target, &ctx.partials)?;
 		let (subject, body, requester) = render_alert(&tera, &mut tera_ctx)?;
+		let retry = target.retry_policy(ctx.retry);
 
 		match target {
 			SendTarget::Email {
@@ -764,7 +1942,7 @@ async fn execute_alert(
 					}),
 				..
 			} => {
-				if dry_run {
+				if dry_run.is_some() {
 					println!("-------------------------------");
 					println!("Alert: {}", alert.file.display());
 					println!("Recipients: {}", addresses.join(", "));
@@ -788,10 +1966,13 @@ async fn execute_alert(
 					html: body,
 					..Default::default()
 				};
-				mailgun
-					.async_send(mailgun_rs::MailgunRegion::US, &sender, message)
+				retry
+					.run(|| async {
+						mailgun
+							.async_send(mailgun_rs::MailgunRegion::US, &sender, message.clone())
+							.await
+					})
 					.await
-					.into_diagnostic()
 					.wrap_err("sending email")?;
 			}
 
@@ -819,7 +2000,7 @@ async fn execute_alert(
 					}),
 				..
 			} => {
-				if dry_run {
+				if dry_run.is_some() {
 					println!("-------------------------------");
 					println!("Alert: {}", alert.file.display());
 					println!("Endpoint: {}", endpoint);
@@ -838,35 +2019,240 @@ async fn execute_alert(
 					}
 				});
 
-				let mut req_builder = ctx.http_client.post(endpoint.clone()).json(&req);
+				let resp = retry
+					.run(|| async {
+						let mut req_builder = ctx.http_client.post(endpoint.clone()).json(&req);
+
+						if let ZendeskMethod::Authorized {
+							credentials: ZendeskCredentials { email, password },
+						} = method
+						{
+							req_builder = req_builder
+								.basic_auth(std::format_args!("{email}/token"), Some(password));
+						}
+
+						req_builder
+							.send()
+							.await
+							.and_then(|resp| resp.error_for_status())
+					})
+					.await
+					.wrap_err("creating Zendesk ticket")?;
+				debug!(resp_text = ?resp.text().await.into_diagnostic()?, "Zendesk ticket sent");
+			}
 
-				if let ZendeskMethod::Authorized {
-					credentials: ZendeskCredentials { email, password },
-				} = method
-				{
-					req_builder =
-						req_builder.basic_auth(std::format_args!("{email}/token"), Some(password));
+			SendTarget::Slack { conn, .. }
+			| SendTarget::External {
+				resolved: Some(ExternalTarget::Slack { conn, .. }),
+				..
+			} => {
+				if dry_run.is_some() {
+					println!("-------------------------------");
+					println!("Alert: {}", alert.file.display());
+					println!(
+						"Slack webhook: {}",
+						conn.webhook.host_str().unwrap_or_default()
+					);
+					println!("Subject: {subject}");
+					println!("Body: {body}");
+					continue;
 				}
 
-				let resp = req_builder
-					.send()
+				debug!(channel=?conn.channel, "posting to Slack");
+				let payload = conn.payload(&subject, &body);
+				retry
+					.run(|| async {
+						ctx.http_client
+							.post(conn.webhook.clone())
+							.json(&payload)
+							.send()
+							.await
+							.and_then(|resp| resp.error_for_status())
+					})
 					.await
-					.into_diagnostic()
-					.wrap_err("creating Zendesk ticket")?;
-				debug!(resp_text = ?resp.text().await.into_diagnostic()?, "Zendesk ticket sent");
+					.wrap_err("posting to Slack")?;
+			}
+
+			SendTarget::Webhook { conn, .. }
+			| SendTarget::External {
+				resolved: Some(ExternalTarget::Webhook { conn, .. }),
+				..
+			} => {
+				let headers = render_headers(&tera, conn, &tera_ctx)?;
+				let body = conn.format.encode(&body)?;
+
+				if dry_run.is_some() {
+					let request = conn
+						.request(&ctx.http_client, &headers, &body)
+						.build()
+						.into_diagnostic()
+						.wrap_err("building webhook request")?;
+					println!("-------------------------------");
+					println!("Alert: {}", alert.file.display());
+					println!("Webhook: {} {}", conn.method, conn.url);
+					for (name, _) in &headers {
+						println!("Header: {name}");
+					}
+					println!("Subject: {subject}");
+					println!(
+						"Body: {}",
+						request
+							.body()
+							.and_then(|body| body.as_bytes())
+							.map(String::from_utf8_lossy)
+							.unwrap_or_default()
+					);
+					continue;
+				}
+
+				debug!(method=%conn.method, url=%conn.url, "calling webhook");
+				retry
+					.run(|| async {
+						conn.request(&ctx.http_client, &headers, &body)
+							.send()
+							.await
+							.and_then(|resp| resp.error_for_status())
+					})
+					.await
+					.wrap_err("calling webhook")?;
 			}
 
 			SendTarget::External {
 				resolved: None, id, ..
 			} => {
 				error!(?id, "external send target not found");
+				continue;
 			}
 		}
+
+		// recorded as each target succeeds, so a failure doesn't repeat the ones already sent
+		if alert.cooldown.is_some() && dry_run.is_none() {
+			state.record(&alert.file, index, dedup_key.clone(), now);
+		}
 	}
 
 	Ok(())
 }
 
+/// Describe what an alert would do, for `--dry-run-format json`.
+///
+/// Templates are only rendered if the alert would fire. Webhook bodies and headers are encoded and
+/// rendered as they would be when sending, so errors in those show up too.
+fn dry_run_report(
+	alert: &AlertDefinition,
+	sources: Result<(ControlFlow<()>, Option<usize>)>,
+	partials: &Partials,
+	context: &mut TeraCtx,
+) -> serde_json::Value {
+	let (fired, rows, error) = match sources {
+		Ok((flow, rows)) => (flow.is_continue(), rows, None),
+		Err(err) => (false, None, Some(error_chain(&err))),
+	};
+
+	let targets = alert
+		.send
+		.iter()
+		.map(|target| {
+			let mut report = match target {
+				SendTarget::Email {
+					conn: TargetEmail { addresses },
+					..
+				} => json!({ "target": "email", "addresses": addresses }),
+				SendTarget::Zendesk {
+					conn: TargetZendesk { endpoint, .. },
+					..
+				} => json!({ "target": "zendesk", "endpoint": endpoint }),
+				SendTarget::Slack { conn, .. } => {
+					json!({ "target": "slack", "channel": conn.channel })
+				}
+				SendTarget::Webhook { conn, .. } => {
+					json!({ "target": "webhook", "method": conn.method.as_str(), "url": conn.url })
+				}
+				SendTarget::External {
+					id,
+					resolved:
+						Some(ExternalTarget::Email {
+							conn: TargetEmail { addresses },
+							..
+						}),
+					..
+				} => json!({ "target": "email", "id": id, "addresses": addresses }),
+				SendTarget::External {
+					id,
+					resolved:
+						Some(ExternalTarget::Zendesk {
+							conn: TargetZendesk { endpoint, .. },
+							..
+						}),
+					..
+				} => json!({ "target": "zendesk", "id": id, "endpoint": endpoint }),
+				SendTarget::External {
+					id,
+					resolved: Some(ExternalTarget::Slack { conn, .. }),
+					..
+				} => json!({ "target": "slack", "id": id, "channel": conn.channel }),
+				SendTarget::External {
+					id,
+					resolved: Some(ExternalTarget::Webhook { conn, .. }),
+					..
+				} => {
+					json!({ "target": "webhook", "id": id, "method": conn.method.as_str(), "url": conn.url })
+				}
+				SendTarget::External {
+					id, resolved: None, ..
+				} => {
+					return json!({ "target": "external", "id": id, "resolved": false });
+				}
+			};
+			report["resolved"] = true.into();
+
+			if fired {
+				let rendered = load_templates(target, partials).and_then(|tera| {
+					let (subject, body, _) = render_alert(&tera, context)?;
+					if let SendTarget::Webhook { conn, .. }
+					| SendTarget::External {
+						resolved: Some(ExternalTarget::Webhook { conn, .. }),
+						..
+					} = target
+					{
+						render_headers(&tera, conn, context)?;
+						conn.format.encode(&body)?;
+					}
+					Ok((subject, body))
+				});
+				match rendered {
+					Ok((subject, body)) => {
+						report["rendered"] = true.into();
+						report["subject"] = subject.into();
+						report["body"] = body.into();
+					}
+					Err(err) => {
+						report["rendered"] = false.into();
+						report["error"] = error_chain(&err).into();
+					}
+				}
+			}
+
+			report
+		})
+		.collect::<Vec<_>>();
+
+	json!({
+		"file": alert.file,
+		"fired": fired,
+		"rows": rows,
+		"targets": targets,
+		"error": error,
+	})
+}
+
+fn error_chain(err: &miette::Report) -> String {
+	err.chain()
+		.map(ToString::to_string)
+		.collect::<Vec<_>>()
+		.join(": ")
+}
+
 #[derive(Debug)]
 struct Interval(pub Duration);
 
@@ -896,7 +2282,12 @@ mod tests {
 			file: PathBuf::from("test.yaml"),
 			enabled: true,
 			interval: dur.to_std().unwrap(),
+			own_interval: None,
 			source: TicketSource::Sql { sql: "".into() },
+			fire_when_rows: Default::default(),
+			severity: Default::default(),
+			cooldown: None,
+			dedup_key: None,
 			send: vec![],
 			recipients: vec![],
 			subject: None,
@@ -908,6 +2299,312 @@ mod tests {
 			.map(|s| s.to_owned())
 	}
 
+	#[test]
+	fn test_alert_parse_cooldown() {
+		let alert = r#"
+sql: SELECT $1::timestamptz;
+cooldown: 6h
+dedup_key: "{{ rows | length }}"
+"#;
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		assert_eq!(
+			alert.cooldown,
+			Some(std::time::Duration::from_secs(6 * 60 * 60))
+		);
+		assert_eq!(alert.dedup_key.as_deref(), Some("{{ rows | length }}"));
+
+		let alert: AlertDefinition = serde_yml::from_str("sql: SELECT 1").unwrap();
+		assert_eq!(alert.cooldown, None);
+
+		assert!(serde_yml::from_str::<AlertDefinition>("sql: SELECT 1\ncooldown: soon").is_err());
+	}
+
+	#[test]
+	fn test_alert_parse_severity() {
+		let alert: AlertDefinition =
+			serde_yml::from_str("sql: SELECT 1\nseverity: critical").unwrap();
+		assert_eq!(alert.severity, Severity::Critical);
+
+		let mut alert: AlertDefinition = serde_yml::from_str("sql: SELECT 1").unwrap();
+		assert_eq!(alert.severity, Severity::Warning);
+		alert.file = PathBuf::from("alerts/jobs.yml");
+		assert_eq!(
+			build_context(&alert, Utc::now())
+				.get("severity")
+				.and_then(|v| v.as_str()),
+			Some("warning")
+		);
+
+		assert!(serde_yml::from_str::<AlertDefinition>("sql: SELECT 1\nseverity: dire").is_err());
+
+		let targets: AlertTargets = serde_yml::from_str(
+			r#"
+targets:
+- id: email-staff
+  target: email
+  addresses: [staff@job.com]
+- id: oncall-pager
+  target: webhook
+  url: https://events.example.com/page
+  severities: [warning, critical]
+"#,
+		)
+		.unwrap();
+		let targets = targets.into_map();
+		let Some(ExternalTarget::Webhook { severities, .. }) = targets.get("oncall-pager") else {
+			panic!(
+				"expected a webhook target, got {:?}",
+				targets.get("oncall-pager")
+			);
+		};
+		assert_eq!(severities, &[Severity::Warning, Severity::Critical]);
+		assert!(targets["email-staff"].accepts(Severity::Info));
+	}
+
+	#[test]
+	fn test_severity_routing() {
+		let targets: AlertTargets = serde_yml::from_str(
+			r#"
+targets:
+- id: email-staff
+  target: email
+  addresses: [staff@job.com]
+- id: oncall-pager
+  target: webhook
+  url: https://events.example.com/page
+  severities: [critical]
+"#,
+		)
+		.unwrap();
+		let targets = targets.into_map();
+
+		let routed = |severity: &str| {
+			let alert: AlertDefinition = serde_yml::from_str(&format!(
+				r#"
+sql: SELECT 1
+severity: {severity}
+send:
+- target: external
+  id: email-staff
+- target: external
+  id: oncall-pager
+- target: external
+  id: missing
+"#
+			))
+			.unwrap();
+			alert
+				.normalise(&targets)
+				.send
+				.into_iter()
+				.map(|target| match target {
+					SendTarget::External { id, .. } => id,
+					other => panic!("expected an external target, got {other:?}"),
+				})
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			routed("critical"),
+			["email-staff", "oncall-pager", "missing"]
+		);
+		assert_eq!(routed("info"), ["email-staff", "missing"]);
+	}
+
+	#[test]
+	fn test_target_retry_policy() {
+		let targets: AlertTargets = serde_yml::from_str(
+			r#"
+targets:
+- id: slack-oncall
+  target: slack
+  webhook: https://hooks.slack.com/services/T000/B000/XXXX
+  retry:
+    attempts: 5
+    delay: 1m
+"#,
+		)
+		.unwrap();
+		let alert: AlertDefinition = serde_yml::from_str(
+			r#"
+sql: SELECT 1
+send:
+- target: external
+  id: slack-oncall
+- target: external
+  id: slack-oncall
+  retry:
+    delay: 10s
+- target: email
+  addresses: [staff@job.com]
+"#,
+		)
+		.unwrap();
+		let alert = alert.normalise(&targets.into_map());
+
+		let default = RetryPolicy {
+			attempts: 3,
+			delay: std::time::Duration::from_secs(5),
+		};
+		assert_eq!(
+			alert
+				.send
+				.iter()
+				.map(|target| target.retry_policy(default))
+				.map(|policy| (policy.attempts, policy.delay.as_secs()))
+				.collect::<Vec<_>>(),
+			[(5, 60), (5, 10), (3, 5)]
+		);
+	}
+
+	#[test]
+	fn test_args_send_attempts() {
+		let parse = |attempts: &str| {
+			AlertsArgs::try_parse_from(["alerts", "--interval", "1h", "--send-attempts", attempts])
+		};
+		assert_eq!(parse("1").unwrap().send_attempts, 1);
+		assert!(parse("0").is_err());
+	}
+
+	#[test]
+	fn test_cooldown_suppresses_repeats() {
+		let file = Path::new("alerts/jobs.yml");
+		let cooldown = std::time::Duration::from_secs(60 * 60);
+		let start = Utc::now();
+		let mut cooldowns = State::default();
+
+		// run every 15 minutes for two hours, with the condition always matching
+		let mut sent = Vec::new();
+		for run in 0..8 {
+			let now = start + Duration::minutes(15 * run);
+			if !cooldowns.is_cooling_down(file, 0, "", cooldown, now) {
+				cooldowns.record(file, 0, String::new(), now);
+				sent.push(run);
+			}
+		}
+		assert_eq!(sent, [0, 4]);
+
+		// a different key isn't a repeat
+		let now = start + Duration::minutes(105);
+		assert!(cooldowns.is_cooling_down(file, 0, "", cooldown, now));
+		assert!(!cooldowns.is_cooling_down(file, 0, "1,2,", cooldown, now));
+
+		// nor is another alert
+		assert!(!cooldowns.is_cooling_down(Path::new("alerts/other.yml"), 0, "", cooldown, now));
+	}
+
+	#[test]
+	fn test_cooldown_partial_failure() {
+		let file = Path::new("alerts/jobs.yml");
+		let cooldown = std::time::Duration::from_secs(60 * 60);
+		let start = Utc::now();
+		let mut cooldowns = State::default();
+
+		// the first target succeeds, the second fails
+		cooldowns.record(file, 0, String::new(), start);
+
+		// on the next run, only the second target is tried again
+		let now = start + Duration::minutes(15);
+		let retried = (0..2)
+			.filter(|&target| !cooldowns.is_cooling_down(file, target, "", cooldown, now))
+			.collect::<Vec<_>>();
+		assert_eq!(retried, [1]);
+	}
+
+	#[test]
+	fn test_state_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("state.json");
+		assert_eq!(State::load(&path), State::default());
+
+		let mut state = State::default();
+		state.record(Path::new("alerts/jobs.yml"), 1, "1,2,".into(), Utc::now());
+		state
+			.last_run
+			.insert(PathBuf::from("alerts/jobs.yml"), Utc::now());
+		state.save(&path).unwrap();
+		assert_eq!(State::load(&path), state);
+
+		std::fs::write(&path, "not json").unwrap();
+		assert_eq!(State::load(&path), State::default());
+	}
+
+	#[test]
+	fn test_interval_clamped_to_min() {
+		let file = Path::new("alerts/busy.yml");
+		let hour = std::time::Duration::from_secs(60 * 60);
+		let min = std::time::Duration::from_secs(5 * 60);
+
+		let second = std::time::Duration::from_secs(1);
+		assert_eq!(resolve_interval(file, Some(second), hour, Some(min)), min);
+		assert_eq!(resolve_interval(file, Some(hour), hour, Some(min)), hour);
+		assert_eq!(resolve_interval(file, Some(second), hour, None), second);
+	}
+
+	#[test]
+	fn test_interval_default() {
+		let file = Path::new("alerts/jobs.yml");
+		let hour = std::time::Duration::from_secs(60 * 60);
+
+		let alert: AlertDefinition = serde_yml::from_str("sql: SELECT 1").unwrap();
+		assert_eq!(alert.own_interval, None);
+		assert_eq!(resolve_interval(file, alert.own_interval, hour, None), hour);
+
+		let alert: AlertDefinition = serde_yml::from_str("sql: SELECT 1\ninterval: 1d").unwrap();
+		assert_eq!(alert.own_interval, Some(hour * 24));
+		assert_eq!(
+			resolve_interval(file, alert.own_interval, hour, None),
+			hour * 24
+		);
+	}
+
+	#[test]
+	fn test_interval_due() {
+		let file = Path::new("alerts/daily.yml");
+		let day = std::time::Duration::from_secs(24 * 60 * 60);
+		let tolerance = std::time::Duration::from_secs(30 * 60);
+		let start = Utc::now();
+
+		let mut state = State::default();
+		assert!(state.is_due(file, day, tolerance, start));
+		state.last_run.insert(file.into(), start);
+
+		assert!(!state.is_due(file, day, tolerance, start + Duration::hours(1)));
+		assert!(!state.is_due(file, day, tolerance, start + Duration::hours(23)));
+		// the scheduler ran us a bit early
+		assert!(state.is_due(
+			file,
+			day,
+			tolerance,
+			start + Duration::hours(24) - Duration::minutes(1)
+		));
+		assert!(state.is_due(file, day, tolerance, start + Duration::hours(25)));
+	}
+
+	#[test]
+	fn test_jitter_offset() {
+		let jitter = std::time::Duration::from_secs(60);
+		let offsets = [
+			"alerts/a.yml",
+			"alerts/b.yml",
+			"alerts/c.yml",
+			"alerts/d.yml",
+		]
+		.map(|file| jitter_offset(Path::new(file), jitter));
+		assert!(offsets.iter().all(|offset| *offset <= jitter));
+		assert!(offsets
+			.iter()
+			.enumerate()
+			.all(|(i, offset)| !offsets[..i].contains(offset)));
+
+		// the same alert gets the same offset every time
+		assert_eq!(jitter_offset(Path::new("alerts/a.yml"), jitter), offsets[0]);
+		assert_eq!(
+			jitter_offset(Path::new("alerts/a.yml"), std::time::Duration::ZERO),
+			std::time::Duration::ZERO
+		);
+	}
+
 	#[test]
 	fn test_interval_format_minutes() {
 		assert_eq!(
@@ -938,7 +2635,7 @@ send:
     <p>Server: {{ hostname }}</p>
     <p>There are {{ rows | length }} rows.</p>
 "#;
-		let alert: AlertDefinition = serde_yml::from_str(&alert).unwrap();
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
 		let alert = alert.normalise(&Default::default());
 		assert_eq!(alert.interval, std::time::Duration::default());
 		assert!(
@@ -953,7 +2650,7 @@ send:
 shell: bash
 run: echo foobar
 "#;
-		let alert: AlertDefinition = serde_yml::from_str(&alert).unwrap();
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
 		let alert = alert.normalise(&Default::default());
 		assert_eq!(alert.interval, std::time::Duration::default());
 		assert!(
@@ -961,47 +2658,163 @@ run: echo foobar
 		);
 	}
 
+	#[test]
+	fn test_alert_parse_fire_when_rows() {
+		let alert = r#"
+sql: SELECT 1
+fire_when_rows: "> 5"
+"#;
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		assert_eq!(
+			alert.fire_when_rows,
+			RowsThreshold {
+				op: RowsComparison::Greater,
+				count: 5
+			}
+		);
+
+		let alert = r#"
+sql: SELECT 1
+fire_when_rows: 3
+"#;
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		assert_eq!(
+			alert.fire_when_rows,
+			RowsThreshold {
+				op: RowsComparison::GreaterOrEqual,
+				count: 3
+			}
+		);
+
+		let alert = r#"
+sql: SELECT 1
+"#;
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		assert_eq!(alert.fire_when_rows, RowsThreshold::default());
+
+		let alert = r#"
+sql: SELECT 1
+fire_when_rows: "=> 5"
+"#;
+		assert!(serde_yml::from_str::<AlertDefinition>(alert).is_err());
+	}
+
+	#[test]
+	fn test_rows_threshold_parse() {
+		let parse =
+			|spec: &str| RowsThreshold::try_from(RowsThresholdSpec::Comparison(spec.into()));
+		assert_eq!(
+			parse(">=5"),
+			Ok(RowsThreshold {
+				op: RowsComparison::GreaterOrEqual,
+				count: 5
+			})
+		);
+		assert_eq!(
+			parse(" == 0 "),
+			Ok(RowsThreshold {
+				op: RowsComparison::Equal,
+				count: 0
+			})
+		);
+		assert_eq!(
+			parse("!= 2"),
+			Ok(RowsThreshold {
+				op: RowsComparison::NotEqual,
+				count: 2
+			})
+		);
+		assert_eq!(
+			parse("<= 10"),
+			Ok(RowsThreshold {
+				op: RowsComparison::LessOrEqual,
+				count: 10
+			})
+		);
+		assert_eq!(
+			parse("< 1"),
+			Ok(RowsThreshold {
+				op: RowsComparison::Less,
+				count: 1
+			})
+		);
+		assert_eq!(
+			parse("7"),
+			Ok(RowsThreshold {
+				op: RowsComparison::GreaterOrEqual,
+				count: 7
+			})
+		);
+		assert!(parse("about 5").is_err());
+		assert!(parse("> -1").is_err());
+		assert!(parse(">").is_err());
+	}
+
+	#[test]
+	fn test_rows_threshold_boundaries() {
+		let threshold = |op, count| RowsThreshold { op, count };
+
+		let default = RowsThreshold::default();
+		assert!(!default.matches(0));
+		assert!(default.matches(1));
+		assert!(default.matches(100));
+
+		let gt = threshold(RowsComparison::Greater, 5);
+		assert!(!gt.matches(4));
+		assert!(!gt.matches(5));
+		assert!(gt.matches(6));
+
+		let ge = threshold(RowsComparison::GreaterOrEqual, 5);
+		assert!(!ge.matches(4));
+		assert!(ge.matches(5));
+		assert!(ge.matches(6));
+
+		let eq = threshold(RowsComparison::Equal, 0);
+		assert!(eq.matches(0));
+		assert!(!eq.matches(1));
+
+		let ne = threshold(RowsComparison::NotEqual, 2);
+		assert!(ne.matches(1));
+		assert!(!ne.matches(2));
+		assert!(ne.matches(3));
+
+		let le = threshold(RowsComparison::LessOrEqual, 5);
+		assert!(le.matches(4));
+		assert!(le.matches(5));
+		assert!(!le.matches(6));
+
+		let lt = threshold(RowsComparison::Less, 5);
+		assert!(lt.matches(4));
+		assert!(!lt.matches(5));
+		assert!(!lt.matches(6));
+	}
+
 	#[test]
 	fn test_alert_parse_invalid_source() {
 		let alert = r#"
 shell: bash
 "#;
-		assert!(matches!(
-			serde_yml::from_str::<AlertDefinition>(&alert),
-			Err(_)
-		));
+		assert!(serde_yml::from_str::<AlertDefinition>(alert).is_err());
 		let alert = r#"
 run: echo foo
 "#;
-		assert!(matches!(
-			serde_yml::from_str::<AlertDefinition>(&alert),
-			Err(_)
-		));
+		assert!(serde_yml::from_str::<AlertDefinition>(alert).is_err());
 		let alert = r#"
 sql: SELECT $1::timestamptz;
 run: echo foo
 "#;
-		assert!(matches!(
-			serde_yml::from_str::<AlertDefinition>(&alert),
-			Err(_)
-		));
+		assert!(serde_yml::from_str::<AlertDefinition>(alert).is_err());
 		let alert = r#"
 sql: SELECT $1::timestamptz;
 shell: bash
 "#;
-		assert!(matches!(
-			serde_yml::from_str::<AlertDefinition>(&alert),
-			Err(_)
-		));
+		assert!(serde_yml::from_str::<AlertDefinition>(alert).is_err());
 		let alert = r#"
 sql: SELECT $1::timestamptz;
 shell: bash
 run: echo foo
 "#;
-		assert!(matches!(
-			serde_yml::from_str::<AlertDefinition>(&alert),
-			Err(_)
-		));
+		assert!(serde_yml::from_str::<AlertDefinition>(alert).is_err());
 	}
 
 	#[test]
@@ -1016,7 +2829,7 @@ send:
     password: pass
   subject: "[Tamanu Alert] Example ({{ hostname }})"
   template: "Output: {{ output }}""#;
-		let alert: AlertDefinition = serde_yml::from_str(&alert).unwrap();
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
 		assert!(matches!(alert.send[0], SendTarget::Zendesk { .. }));
 	}
 
@@ -1030,7 +2843,7 @@ send:
   requester: "{{ hostname }}"
   subject: "[Tamanu Alert] Example ({{ hostname }})"
   template: "Output: {{ output }}""#;
-		let alert: AlertDefinition = serde_yml::from_str(&alert).unwrap();
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
 		assert!(matches!(alert.send[0], SendTarget::Zendesk { .. }));
 	}
 
@@ -1051,10 +2864,559 @@ send:
   - id: 200
     value: Test
 "#;
-		let alert: AlertDefinition = serde_yml::from_str(&alert).unwrap();
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
 		assert!(matches!(alert.send[0], SendTarget::Zendesk { .. }));
 	}
 
+	#[test]
+	fn test_alert_parse_slack() {
+		let alert = r##"
+sql: SELECT $1::timestamptz;
+send:
+- target: slack
+  webhook: https://hooks.slack.com/services/T000/B000/XXXX
+  channel: "#alerts"
+  username: Tamanu alerts
+  icon_emoji: ":rotating_light:"
+  template: "There are {{ rows | length }} rows."
+"##;
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		let SendTarget::Slack { conn, .. } = &alert.send[0] else {
+			panic!("expected a slack target, got {:?}", alert.send[0]);
+		};
+		assert_eq!(
+			conn.webhook.as_str(),
+			"https://hooks.slack.com/services/T000/B000/XXXX"
+		);
+		assert_eq!(conn.channel.as_deref(), Some("#alerts"));
+		assert_eq!(conn.username.as_deref(), Some("Tamanu alerts"));
+		assert_eq!(conn.icon_emoji.as_deref(), Some(":rotating_light:"));
+		assert_eq!(conn.icon_url, None);
+
+		let targets: AlertTargets = serde_yml::from_str(
+			r#"
+targets:
+- id: slack-oncall
+  target: slack
+  webhook: https://hooks.slack.com/services/T000/B000/XXXX
+"#,
+		)
+		.unwrap();
+		assert!(matches!(
+			targets.into_map().get("slack-oncall"),
+			Some(ExternalTarget::Slack { .. })
+		));
+	}
+
+	#[test]
+	fn test_slack_payload() {
+		let alert = AlertDefinition {
+			file: PathBuf::from("alerts/jobs.yml"),
+			subject: Some("{{ rows | length }} failed jobs".into()),
+			template: Some(
+				"{% for row in rows %}- job {{ row.id }}: {{ row.error }}\n{% endfor %}".into(),
+			),
+			send: vec![SendTarget::Slack {
+				subject: None,
+				template: None,
+				retry: None,
+				conn: TargetSlack {
+					webhook: "https://hooks.slack.com/services/T000/B000/XXXX"
+						.parse()
+						.unwrap(),
+					channel: Some("#alerts".into()),
+					username: None,
+					icon_emoji: Some(":rotating_light:".into()),
+					icon_url: None,
+				},
+			}],
+			..Default::default()
+		}
+		.normalise(&Default::default());
+
+		let mut context = build_context(&alert, Utc::now());
+		context.insert(
+			"rows",
+			&[
+				json!({ "id": 1, "error": "timeout" }),
+				json!({ "id": 2, "error": "conflict" }),
+			],
+		);
+		let tera = load_templates(&alert.send[0], &Partials::default()).unwrap();
+		let (subject, body, _) = render_alert(&tera, &mut context).unwrap();
+
+		let SendTarget::Slack { conn, .. } = &alert.send[0] else {
+			unreachable!();
+		};
+		assert_eq!(
+			conn.payload(&subject, &body),
+			json!({
+				"text": "*2 failed jobs*\n- job 1: timeout\n- job 2: conflict\n",
+				"channel": "#alerts",
+				"icon_emoji": ":rotating_light:",
+			})
+		);
+	}
+
+	#[test]
+	fn test_alert_parse_webhook() {
+		let alert = r#"
+sql: SELECT $1::timestamptz;
+send:
+- target: webhook
+  url: https://example.com/api/alerts
+  method: put
+  headers:
+    Authorization: Bearer abcdef
+  format: form
+  template: '{ "count": {{ rows | length }} }'
+- target: webhook
+  url: https://example.com/api/alerts
+  template: '{}'
+"#;
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		let SendTarget::Webhook { conn, .. } = &alert.send[0] else {
+			panic!("expected a webhook target, got {:?}", alert.send[0]);
+		};
+		assert_eq!(conn.method, reqwest::Method::PUT);
+		assert_eq!(conn.format, WebhookFormat::Form);
+		assert_eq!(conn.headers["Authorization"], "Bearer abcdef");
+
+		let SendTarget::Webhook { conn, .. } = &alert.send[1] else {
+			panic!("expected a webhook target, got {:?}", alert.send[1]);
+		};
+		assert_eq!(conn.method, reqwest::Method::POST);
+		assert_eq!(conn.format, WebhookFormat::Json);
+		assert!(conn.headers.is_empty());
+	}
+
+	fn webhook_alert(format: WebhookFormat) -> AlertDefinition {
+		AlertDefinition {
+			file: PathBuf::from("alerts/jobs.yml"),
+			subject: Some("{{ rows | length }} failed jobs".into()),
+			send: vec![SendTarget::Webhook {
+				subject: None,
+				template: Some(
+					r#"{ "title": "{{ subject }}", "count": {{ rows | length }}, "urgent": true }"#
+						.into(),
+				),
+				retry: None,
+				conn: TargetWebhook {
+					url: "https://example.com/api/alerts".parse().unwrap(),
+					method: reqwest::Method::POST,
+					headers: BTreeMap::from([
+						("Authorization".into(), "Bearer abcdef".into()),
+						(
+							"X-Alert-Source".into(),
+							"{{ hostname }}/{{ filename }}".into(),
+						),
+					]),
+					format,
+				},
+			}],
+			..Default::default()
+		}
+		.normalise(&Default::default())
+	}
+
+	fn render_webhook(alert: &AlertDefinition) -> (Vec<(String, String)>, WebhookBody) {
+		let mut context = build_context(alert, Utc::now());
+		context.insert("hostname", "tamanu-server");
+		context.insert("rows", &[json!({ "id": 1 }), json!({ "id": 2 })]);
+		let tera = load_templates(&alert.send[0], &Partials::default()).unwrap();
+		let (_, body, _) = render_alert(&tera, &mut context).unwrap();
+
+		let SendTarget::Webhook { conn, .. } = &alert.send[0] else {
+			unreachable!();
+		};
+		(
+			render_headers(&tera, conn, &context).unwrap(),
+			conn.format.encode(&body).unwrap(),
+		)
+	}
+
+	#[test]
+	fn test_webhook_headers() {
+		let (headers, _) = render_webhook(&webhook_alert(WebhookFormat::Json));
+		assert_eq!(
+			headers,
+			[
+				("Authorization".into(), "Bearer abcdef".into()),
+				("X-Alert-Source".into(), "tamanu-server/jobs.yml".into()),
+			]
+		);
+	}
+
+	#[test]
+	fn test_webhook_body() {
+		let (_, body) = render_webhook(&webhook_alert(WebhookFormat::Json));
+		assert_eq!(
+			body,
+			WebhookBody::Json(json!({ "title": "2 failed jobs", "count": 2, "urgent": true }))
+		);
+
+		let (_, body) = render_webhook(&webhook_alert(WebhookFormat::Form));
+		assert_eq!(
+			body,
+			WebhookBody::Form(vec![
+				("title".into(), "2 failed jobs".into()),
+				("count".into(), "2".into()),
+				("urgent".into(), "true".into()),
+			])
+		);
+
+		assert!(WebhookFormat::Json.encode("not json").is_err());
+		assert!(WebhookFormat::Form
+			.encode(r#"{ "nested": { "a": 1 } }"#)
+			.is_err());
+	}
+
+	fn email_target(template: &str) -> SendTarget {
+		SendTarget::Email {
+			subject: Some("Alert".into()),
+			template: Some(template.into()),
+			retry: None,
+			conn: TargetEmail {
+				addresses: vec!["test@example.com".into()],
+			},
+		}
+	}
+
+	fn partials(list: &[(&str, &str)]) -> Partials {
+		Partials {
+			templates: list
+				.iter()
+				.map(|(name, content)| (name.to_string(), content.to_string()))
+				.collect(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_template_includes() {
+		assert_eq!(
+			template_includes(
+				r#"{% include "header" %} {{ rows }} {%- include 'footer' -%}
+				{% if x %}{% include ["a", "b"] ignore missing %}{% endif %} {% included %}"#
+			),
+			vec![
+				("header", false),
+				("footer", false),
+				("a", true),
+				("b", true)
+			]
+		);
+	}
+
+	#[test]
+	fn test_template_partials_render() {
+		let partials = partials(&[
+			("header", "<h1>{{ hostname }}</h1>"),
+			("footer", "{% include \"common/sig\" %}"),
+			("common/sig", "-- BES"),
+		]);
+		assert!(partials.find_error().is_none());
+
+		let tera = load_templates(
+			&email_target(r#"{% include "header" %}<p>body</p>{% include "footer" %}"#),
+			&partials,
+		)
+		.unwrap();
+		let mut context = TeraCtx::new();
+		context.insert("hostname", "tamanu");
+		let (_, body, _) = render_alert(&tera, &mut context).unwrap();
+		assert_eq!(body, "<h1>tamanu</h1><p>body</p>-- BES");
+	}
+
+	#[test]
+	fn test_template_partials_missing() {
+		let err = load_templates(
+			&email_target(r#"{% include "header" %}"#),
+			&partials(&[("footer", "-- BES")]),
+		)
+		.err()
+		.unwrap();
+		assert_eq!(
+			err.to_string(),
+			r#"template "alert.html" includes "header", which doesn't exist"#
+		);
+
+		let partials = partials(&[("header", "{% include \"nope\" %}")]);
+		let (names, err) = partials.find_error().unwrap();
+		assert_eq!(names, ["header"]);
+		assert_eq!(
+			err.to_string(),
+			r#"template "header" includes "nope", which doesn't exist"#
+		);
+	}
+
+	#[test]
+	fn test_template_partials_cycle() {
+		let partials = partials(&[
+			("a", "{% include \"b\" %}"),
+			("b", "{% include \"c\" %}"),
+			("c", "{% include \"a\" %}"),
+		]);
+		let (mut names, err) = partials.find_error().unwrap();
+		let err = err.to_string();
+		assert!(
+			err.starts_with("template partials include each other in a cycle:"),
+			"{err}"
+		);
+		names.sort();
+		assert_eq!(names, ["a", "b", "c"]);
+	}
+
+	#[test]
+	fn test_template_partials_broken() {
+		let mut partials = partials(&[
+			("header", "<h1>{{ hostname }}</h1>"),
+			("unclosed", "{% if rows %}"),
+			("footer", "{% include \"unclosed\" %}"),
+			("a", "{% include \"b\" %}"),
+			("b", "{% include \"a\" %}"),
+			("typo", "{% include \"heade\" %}"),
+		]);
+		partials.remove_broken();
+		assert!(partials.find_error().is_none());
+		assert_eq!(partials.templates.keys().collect::<Vec<_>>(), ["header"]);
+		assert_eq!(
+			partials.broken,
+			HashSet::from(["unclosed", "footer", "a", "b", "typo"].map(String::from))
+		);
+
+		load_templates(&email_target(r#"{% include "header" %}"#), &partials).unwrap();
+		let err = load_templates(
+			&email_target(r#"{% include "header" %}{% include "footer" %}"#),
+			&partials,
+		)
+		.err()
+		.unwrap();
+		assert_eq!(
+			err.to_string(),
+			r#"template "alert.html" includes "footer", which has errors"#
+		);
+	}
+
+	#[test]
+	fn test_dry_run_report() {
+		let alert = AlertDefinition {
+			file: PathBuf::from("alerts/jobs.yml"),
+			send: vec![
+				email_target("{{ rows | length }} failed jobs"),
+				SendTarget::External {
+					subject: None,
+					template: None,
+					retry: None,
+					id: "nowhere".into(),
+					resolved: None,
+				},
+			],
+			..Default::default()
+		};
+		let now = Utc::now();
+
+		let mut context = build_context(&alert, now);
+		context.insert("rows", &[json!({ "id": 1 }), json!({ "id": 2 })]);
+		let report = dry_run_report(
+			&alert,
+			Ok((ControlFlow::Continue(()), Some(2))),
+			&Partials::default(),
+			&mut context,
+		);
+		assert_eq!(report["file"], "alerts/jobs.yml");
+		assert_eq!(report["fired"], true);
+		assert_eq!(report["rows"], 2);
+		assert_eq!(report["error"], serde_json::Value::Null);
+		assert_eq!(
+			report["targets"][0],
+			json!({
+				"target": "email",
+				"addresses": ["test@example.com"],
+				"resolved": true,
+				"rendered": true,
+				"subject": "Alert",
+				"body": "2 failed jobs",
+			})
+		);
+		assert_eq!(
+			report["targets"][1],
+			json!({ "target": "external", "id": "nowhere", "resolved": false })
+		);
+
+		let report = dry_run_report(
+			&alert,
+			Ok((ControlFlow::Break(()), Some(0))),
+			&Partials::default(),
+			&mut build_context(&alert, now),
+		);
+		assert_eq!(report["fired"], false);
+		assert_eq!(report["rows"], 0);
+		assert_eq!(report["targets"][0]["rendered"], serde_json::Value::Null);
+
+		let report = dry_run_report(
+			&alert,
+			Err(miette!("connection refused").wrap_err("querying database")),
+			&Partials::default(),
+			&mut build_context(&alert, now),
+		);
+		assert_eq!(report["fired"], false);
+		assert_eq!(report["rows"], serde_json::Value::Null);
+		assert_eq!(report["error"], "querying database: connection refused");
+	}
+
+	#[test]
+	fn test_dry_run_report_render_error() {
+		let alert = AlertDefinition {
+			file: PathBuf::from("alerts/jobs.yml"),
+			send: vec![email_target("{{ rows | nonexistent_filter }}")],
+			..Default::default()
+		};
+		let mut context = build_context(&alert, Utc::now());
+		context.insert("rows", &[json!({ "id": 1 })]);
+		let report = dry_run_report(
+			&alert,
+			Ok((ControlFlow::Continue(()), Some(1))),
+			&Partials::default(),
+			&mut context,
+		);
+		assert_eq!(report["fired"], true);
+		assert_eq!(report["targets"][0]["rendered"], false);
+		assert!(report["targets"][0]["error"].is_string());
+	}
+
+	#[test]
+	fn test_dry_run_report_webhook_errors() {
+		let mut context = build_context(&webhook_alert(WebhookFormat::Json), Utc::now());
+		context.insert("hostname", "tamanu-server");
+		context.insert("rows", &[json!({ "id": 1 })]);
+		let report = |alert: &AlertDefinition, context: &mut TeraCtx| {
+			dry_run_report(
+				alert,
+				Ok((ControlFlow::Continue(()), Some(1))),
+				&Partials::default(),
+				context,
+			)
+		};
+
+		let alert = webhook_alert(WebhookFormat::Json);
+		assert_eq!(
+			report(&alert, &mut context.clone())["targets"][0]["rendered"],
+			true
+		);
+
+		let mut alert = webhook_alert(WebhookFormat::Json);
+		let SendTarget::Webhook { template, .. } = &mut alert.send[0] else {
+			unreachable!();
+		};
+		*template = Some("{{ rows | length }} failed jobs".into());
+		let target = &report(&alert, &mut context.clone())["targets"][0];
+		assert_eq!(target["rendered"], false);
+		assert!(
+			target["error"]
+				.as_str()
+				.unwrap()
+				.starts_with("webhook body must be a JSON object"),
+			"{target}"
+		);
+
+		let mut alert = webhook_alert(WebhookFormat::Json);
+		let SendTarget::Webhook { conn, .. } = &mut alert.send[0] else {
+			unreachable!();
+		};
+		conn.headers
+			.insert("X-Broken".into(), "{{ nonexistent }}".into());
+		let target = &report(&alert, &mut context.clone())["targets"][0];
+		assert_eq!(target["rendered"], false);
+		assert!(
+			target["error"]
+				.as_str()
+				.unwrap()
+				.contains("rendering X-Broken header template"),
+			"{target}"
+		);
+	}
+
+	#[test]
+	fn test_pid_file_exclusive() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("alerts.pid");
+
+		let first = PidFile::acquire(&path).unwrap();
+		assert_eq!(
+			std::fs::read_to_string(&path).unwrap().trim(),
+			process::id().to_string()
+		);
+
+		let err = PidFile::acquire(&path).unwrap_err();
+		assert!(
+			err.to_string()
+				.starts_with("another instance is already running"),
+			"{err}"
+		);
+
+		drop(first);
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+		let _second = PidFile::acquire(&path).unwrap();
+		assert_eq!(
+			std::fs::read_to_string(&path).unwrap().trim(),
+			process::id().to_string()
+		);
+	}
+
+	#[test]
+	fn test_target_template_overrides() {
+		let alert = r#"
+sql: SELECT 1
+subject: "Alert subject"
+template: "Default body"
+send:
+  - target: email
+    addresses: [test@example.com]
+  - target: email
+    addresses: [other@example.com]
+    subject: "Other subject"
+    template: "Other body"
+"#;
+		let mut alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		alert.file = PathBuf::from("alerts/test.yml");
+		let alert = alert.normalise(&Default::default());
+
+		let rendered = alert
+			.send
+			.iter()
+			.map(|target| {
+				let tera = load_templates(target, &Partials::default()).unwrap();
+				let (subject, body, _) =
+					render_alert(&tera, &mut build_context(&alert, Utc::now())).unwrap();
+				(subject, body)
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			rendered,
+			vec![
+				("Alert subject".into(), "Default body".into()),
+				("Other subject".into(), "Other body".into()),
+			]
+		);
+	}
+
+	#[test]
+	fn test_target_template_missing() {
+		let alert = r#"
+sql: SELECT 1
+send:
+  - target: email
+    addresses: [test@example.com]
+"#;
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
+		let alert = alert.normalise(&Default::default());
+		let err = load_templates(&alert.send[0], &Partials::default())
+			.err()
+			.unwrap();
+		assert_eq!(err.to_string(), "send target has no template");
+	}
+
 	#[test]
 	fn test_alert_parse_legacy_recipients() {
 		let alert = r#"
@@ -1067,7 +3429,7 @@ template: |
   <p>Server: {{ hostname }}</p>
   <p>There are {{ rows | length }} rows.</p>
 "#;
-		let alert: AlertDefinition = serde_yml::from_str(&alert).unwrap();
+		let alert: AlertDefinition = serde_yml::from_str(alert).unwrap();
 		let alert = alert.normalise(&Default::default());
 		assert_eq!(alert.interval, std::time::Duration::default());
 		assert!(matches!(alert.send[0], SendTarget::Email { .. }));
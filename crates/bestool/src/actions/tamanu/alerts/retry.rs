@@ -0,0 +1,236 @@
+use std::{future::Future, num::NonZeroU32, time::Duration};
+
+use miette::{Report, Result};
+use rand::Rng as _;
+use reqwest::StatusCode;
+use tracing::warn;
+
+/// Backoff delays don't grow past this, however many attempts there are.
+const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How to retry sending to a target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+	/// How many times to try in total, including the first.
+	pub attempts: u32,
+
+	/// How long to wait after the first failure, doubling after each one after that.
+	pub delay: Duration,
+}
+
+/// Errors that can tell whether a failed send is worth trying again.
+pub trait Retryable {
+	fn is_retryable(&self) -> bool;
+}
+
+impl Retryable for reqwest::Error {
+	/// Connection errors, where the request never reached the server, and 429 and 5xx responses are
+	/// retried. Anything else is either permanent, like most 4xx responses, or may have happened
+	/// after the server acted on the request, like a timeout: trying again could then send a
+	/// duplicate email or ticket.
+	fn is_retryable(&self) -> bool {
+		self.is_connect()
+			|| self.status().is_some_and(|status| {
+				status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+			})
+	}
+}
+
+/// Per-target overrides of the `--send-attempts` and `--retry-delay` policy.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RetryOverride {
+	attempts: Option<NonZeroU32>,
+	#[serde(default, deserialize_with = "super::deserialize_duration")]
+	delay: Option<Duration>,
+}
+
+impl RetryPolicy {
+	/// Apply a target's overrides, if it has any.
+	pub fn with(self, retry: Option<&RetryOverride>) -> Self {
+		let Some(retry) = retry else {
+			return self;
+		};
+
+		Self {
+			attempts: retry.attempts.map_or(self.attempts, NonZeroU32::get),
+			delay: retry.delay.unwrap_or(self.delay),
+		}
+	}
+
+	/// The delay before the next attempt, after this many have failed, without jitter.
+	fn backoff(&self, failed: u32) -> Duration {
+		self.delay
+			.saturating_mul(2_u32.saturating_pow(failed.saturating_sub(1)))
+			.min(MAX_DELAY)
+	}
+
+	/// Call `send` until it succeeds, fails in a way that isn't [retryable](Retryable), or the
+	/// attempts run out.
+	///
+	/// Waits between attempts with exponential backoff, with jitter so that many alerts failing at
+	/// once don't all retry at the same moment. Returns the last error if every attempt failed.
+	pub async fn run<T, E, F>(&self, mut send: impl FnMut() -> F) -> Result<T>
+	where
+		F: Future<Output = Result<T, E>>,
+		E: Retryable + std::error::Error + Send + Sync + 'static,
+	{
+		let mut failed = 0;
+		loop {
+			let err = match send().await {
+				Ok(value) => return Ok(value),
+				Err(err) => err,
+			};
+
+			failed += 1;
+			if !err.is_retryable() || failed >= self.attempts {
+				let err = Report::from_err(err);
+				return Err(if failed > 1 {
+					err.wrap_err(format!("giving up after {failed} attempts"))
+				} else {
+					err
+				});
+			}
+
+			let backoff = self.backoff(failed);
+			let delay = rand::thread_rng().gen_range(backoff / 2..=backoff);
+			warn!(
+				attempt = failed,
+				of = self.attempts,
+				?delay,
+				"sending failed, retrying: {err}"
+			);
+			tokio::time::sleep(delay).await;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::Cell;
+
+	use super::*;
+
+	const POLICY: RetryPolicy = RetryPolicy {
+		attempts: 4,
+		delay: Duration::from_millis(1),
+	};
+
+	#[derive(Debug, thiserror::Error)]
+	enum TestError {
+		#[error("503 Service Unavailable")]
+		Unavailable,
+		#[error("400 Bad Request")]
+		BadRequest,
+	}
+
+	impl Retryable for TestError {
+		fn is_retryable(&self) -> bool {
+			matches!(self, Self::Unavailable)
+		}
+	}
+
+	/// A target that fails some number of times, then succeeds.
+	struct FlakyTarget {
+		failures: u32,
+		error: fn() -> TestError,
+		attempts: Cell<u32>,
+	}
+
+	impl FlakyTarget {
+		fn new(failures: u32) -> Self {
+			Self {
+				failures,
+				error: || TestError::Unavailable,
+				attempts: Cell::new(0),
+			}
+		}
+
+		async fn send(&self) -> Result<&'static str, TestError> {
+			self.attempts.set(self.attempts.get() + 1);
+			if self.attempts.get() > self.failures {
+				Ok("sent")
+			} else {
+				Err((self.error)())
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn succeeds_first_time() {
+		let target = FlakyTarget::new(0);
+		assert_eq!(POLICY.run(|| target.send()).await.unwrap(), "sent");
+		assert_eq!(target.attempts.get(), 1);
+	}
+
+	#[tokio::test]
+	async fn retries_transient_failures() {
+		let target = FlakyTarget::new(3);
+		assert_eq!(POLICY.run(|| target.send()).await.unwrap(), "sent");
+		assert_eq!(target.attempts.get(), 4);
+	}
+
+	#[tokio::test]
+	async fn permanent_failure_surfaces() {
+		let target = FlakyTarget::new(u32::MAX);
+		let err = POLICY.run(|| target.send()).await.unwrap_err();
+		assert_eq!(target.attempts.get(), 4);
+		assert_eq!(
+			err.chain().map(|err| err.to_string()).collect::<Vec<_>>(),
+			["giving up after 4 attempts", "503 Service Unavailable"]
+		);
+	}
+
+	#[tokio::test]
+	async fn permanent_errors_are_not_retried() {
+		let target = FlakyTarget {
+			error: || TestError::BadRequest,
+			..FlakyTarget::new(1)
+		};
+		let err = POLICY.run(|| target.send()).await.unwrap_err();
+		assert_eq!(err.to_string(), "400 Bad Request");
+		assert_eq!(target.attempts.get(), 1);
+	}
+
+	#[tokio::test]
+	async fn single_attempt() {
+		let target = FlakyTarget::new(1);
+		let policy = POLICY.with(Some(&RetryOverride {
+			attempts: NonZeroU32::new(1),
+			delay: None,
+		}));
+		let err = policy.run(|| target.send()).await.unwrap_err();
+		assert_eq!(err.to_string(), "503 Service Unavailable");
+		assert_eq!(target.attempts.get(), 1);
+	}
+
+	#[test]
+	fn backoff_doubles() {
+		let policy = RetryPolicy {
+			attempts: 20,
+			delay: Duration::from_secs(1),
+		};
+		assert_eq!(
+			(1..=5)
+				.map(|n| policy.backoff(n).as_secs())
+				.collect::<Vec<_>>(),
+			[1, 2, 4, 8, 16]
+		);
+		assert_eq!(policy.backoff(19), MAX_DELAY);
+	}
+
+	#[test]
+	fn overrides() {
+		let retry: RetryOverride = serde_yml::from_str("delay: 30s").unwrap();
+		assert_eq!(
+			POLICY.with(Some(&retry)),
+			RetryPolicy {
+				attempts: 4,
+				delay: Duration::from_secs(30),
+			}
+		);
+		assert_eq!(POLICY.with(None), POLICY);
+		assert!(serde_yml::from_str::<RetryOverride>("tries: 3").is_err());
+		assert!(serde_yml::from_str::<RetryOverride>("attempts: 0").is_err());
+	}
+}
@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone as _, Utc};
+use chrono_tz::Tz;
+use miette::{miette, Report};
+use tera::{Tera, Value};
+
+/// Helpers added to every alert template, on top of Tera's built-ins.
+pub const HELPERS: [&str; 3] = ["format_date", "humanize_number", "truncate_chars"];
+
+/// A Tera instance with the alert template helpers registered.
+pub fn tera() -> Tera {
+	let mut tera = Tera::default();
+	tera.register_filter("format_date", format_date);
+	tera.register_filter("humanize_number", humanize_number);
+	tera.register_filter("truncate_chars", truncate_chars);
+	tera
+}
+
+/// Convert a Tera error to a diagnostic, listing the helpers if an unknown one was used.
+pub fn diagnose(err: tera::Error) -> Report {
+	let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&err);
+	while let Some(current) = source {
+		if let Some(
+			tera::ErrorKind::FilterNotFound(name) | tera::ErrorKind::FunctionNotFound(name),
+		) = current.downcast_ref::<tera::Error>().map(|err| &err.kind)
+		{
+			return miette!(
+				help = format!(
+					"alert templates have Tera's built-in filters and functions, and: {}",
+					HELPERS.join(", ")
+				),
+				"unknown template helper {name:?}"
+			)
+			.wrap_err(err.to_string());
+		}
+		source = current.source();
+	}
+
+	Report::from_err(err)
+}
+
+/// Format a number with thousands separators, rounded to some decimal places.
+///
+/// - `decimals`: how many decimal places to round to (default 0)
+/// - `separator`: the thousands separator (default `,`)
+///
+/// Numbers in strings (e.g. from `numeric` columns) are accepted, and rounded without going through
+/// a float so they keep their precision. Null renders as nothing.
+fn humanize_number(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+	let decimals = match args.get("decimals") {
+		Some(decimals) => decimals.as_u64().ok_or_else(|| {
+			tera::Error::msg("humanize_number: `decimals` must be a positive integer")
+		})? as usize,
+		None => 0,
+	};
+	let separator = match args.get("separator") {
+		Some(separator) => separator
+			.as_str()
+			.ok_or_else(|| tera::Error::msg("humanize_number: `separator` must be a string"))?,
+		None => ",",
+	};
+
+	let formatted = match value {
+		Value::Null => return Ok(Value::String(String::new())),
+		Value::Number(number) if number.is_i64() || number.is_u64() => {
+			// avoid going through a float, which loses precision on large integers
+			if decimals == 0 {
+				number.to_string()
+			} else {
+				format!("{number}.{}", "0".repeat(decimals))
+			}
+		}
+		Value::Number(number) => format!("{:.decimals$}", number.as_f64().unwrap_or_default()),
+		Value::String(string) => round_decimal(string.trim(), decimals).ok_or_else(|| {
+			tera::Error::msg(format!("humanize_number: {string:?} is not a number"))
+		})?,
+		other => {
+			return Err(tera::Error::msg(format!(
+				"humanize_number: expected a number, got {other}"
+			)))
+		}
+	};
+
+	let (sign, unsigned) = match formatted.strip_prefix('-') {
+		Some(unsigned) => ("-", unsigned),
+		None => ("", formatted.as_str()),
+	};
+	let (whole, fraction) = match unsigned.split_once('.') {
+		Some((whole, fraction)) => (whole, Some(fraction)),
+		None => (unsigned, None),
+	};
+
+	let mut grouped = String::from(sign);
+	for (i, digit) in whole.chars().enumerate() {
+		if i > 0 && (whole.len() - i) % 3 == 0 {
+			grouped.push_str(separator);
+		}
+		grouped.push(digit);
+	}
+	if let Some(fraction) = fraction {
+		grouped.push('.');
+		grouped.push_str(fraction);
+	}
+
+	Ok(Value::String(grouped))
+}
+
+/// Round a decimal number to some decimal places, working on its digits.
+///
+/// Halves are rounded away from zero, like Postgres' `round()`. Returns `None` if the string isn't a
+/// plain decimal number.
+fn round_decimal(number: &str, decimals: usize) -> Option<String> {
+	let (sign, unsigned) = match number.strip_prefix('-') {
+		Some(unsigned) => ("-", unsigned),
+		None => ("", number.strip_prefix('+').unwrap_or(number)),
+	};
+	let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+	if (whole.is_empty() && fraction.is_empty())
+		|| !whole
+			.bytes()
+			.chain(fraction.bytes())
+			.all(|b| b.is_ascii_digit())
+	{
+		return None;
+	}
+
+	// the digits to keep, padded with zeros, rounded up if the first dropped one is 5 or more
+	let mut digits: Vec<u8> = whole
+		.bytes()
+		.chain(
+			fraction
+				.bytes()
+				.chain(std::iter::repeat(b'0'))
+				.take(decimals),
+		)
+		.collect();
+	if fraction
+		.as_bytes()
+		.get(decimals)
+		.is_some_and(|&digit| digit >= b'5')
+	{
+		let carried = digits.iter_mut().rev().all(|digit| {
+			if *digit == b'9' {
+				*digit = b'0';
+				true
+			} else {
+				*digit += 1;
+				false
+			}
+		});
+		if carried {
+			digits.insert(0, b'1');
+		}
+	}
+
+	let (whole, fraction) = digits.split_at(digits.len() - decimals);
+	let whole = String::from_utf8_lossy(whole);
+	let whole = match whole.trim_start_matches('0') {
+		"" => "0",
+		whole => whole,
+	};
+	let sign = if digits.iter().all(|&digit| digit == b'0') {
+		""
+	} else {
+		sign
+	};
+
+	Some(if decimals == 0 {
+		format!("{sign}{whole}")
+	} else {
+		format!("{sign}{whole}.{}", String::from_utf8_lossy(fraction))
+	})
+}
+
+/// Format a date or timestamp, in some timezone.
+///
+/// - `format`: a [strftime](https://docs.rs/chrono/latest/chrono/format/strftime/) format
+///   (default `%Y-%m-%d %H:%M`)
+/// - `timezone`: an IANA timezone name, e.g. `Pacific/Auckland` (default `UTC`)
+///
+/// Accepts RFC 3339 and ISO 8601 strings (as returned for `timestamptz`, `timestamp`, and `date`
+/// columns), the `now` variable, and Unix timestamps in seconds. Timestamps without a timezone are
+/// taken to be in UTC. Null renders as nothing.
+fn format_date(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+	let format = match args.get("format") {
+		Some(format) => format
+			.as_str()
+			.ok_or_else(|| tera::Error::msg("format_date: `format` must be a string"))?,
+		None => "%Y-%m-%d %H:%M",
+	};
+	let timezone: Tz = match args.get("timezone") {
+		Some(timezone) => timezone
+			.as_str()
+			.and_then(|timezone| timezone.parse().ok())
+			.ok_or_else(|| {
+				tera::Error::msg(format!("format_date: {timezone} is not a known timezone"))
+			})?,
+		None => Tz::UTC,
+	};
+
+	let timestamp = match value {
+		Value::Null => return Ok(Value::String(String::new())),
+		Value::Number(number) => number
+			.as_i64()
+			.and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+		Value::String(string) => parse_date(string),
+		_ => None,
+	}
+	.ok_or_else(|| tera::Error::msg(format!("format_date: {value} is not a date")))?;
+
+	let mut formatted = String::new();
+	std::fmt::write(
+		&mut formatted,
+		format_args!("{}", timestamp.with_timezone(&timezone).format(format)),
+	)
+	.map_err(|_| tera::Error::msg(format!("format_date: {format:?} is not a valid format")))?;
+	Ok(Value::String(formatted))
+}
+
+fn parse_date(string: &str) -> Option<DateTime<Utc>> {
+	let string = string.trim();
+	if let Ok(timestamp) = DateTime::parse_from_rfc3339(string) {
+		return Some(timestamp.to_utc());
+	}
+
+	// the Display format of the `now` variable
+	let naive = string.strip_suffix(" UTC").unwrap_or(string);
+	["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+		.iter()
+		.find_map(|format| NaiveDateTime::parse_from_str(naive, format).ok())
+		.or_else(|| {
+			NaiveDate::parse_from_str(naive, "%Y-%m-%d")
+				.ok()
+				.and_then(|date| date.and_hms_opt(0, 0, 0))
+		})
+		.map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+/// Shorten a string to at most some number of characters, marking where it was cut.
+///
+/// - `length`: how many characters to keep (default 255)
+/// - `end`: what to add if the string was cut (default `…`)
+///
+/// Like Tera's built-in `truncate`, but counting characters rather than graphemes, rendering
+/// non-strings first, and rendering null as nothing. It has its own name so that the built-in keeps
+/// working as before in existing templates.
+fn truncate_chars(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+	let length = match args.get("length") {
+		Some(length) => length.as_u64().ok_or_else(|| {
+			tera::Error::msg("truncate_chars: `length` must be a positive integer")
+		})? as usize,
+		None => 255,
+	};
+	let end = match args.get("end") {
+		Some(end) => end
+			.as_str()
+			.ok_or_else(|| tera::Error::msg("truncate_chars: `end` must be a string"))?,
+		None => "…",
+	};
+
+	let string = match value {
+		Value::Null => return Ok(Value::String(String::new())),
+		Value::String(string) => string.clone(),
+		other => other.to_string(),
+	};
+
+	Ok(Value::String(match string.char_indices().nth(length) {
+		Some((cut, _)) => format!("{}{end}", &string[..cut]),
+		None => string,
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use tera::Context;
+
+	use super::*;
+
+	fn render(template: &str, value: Value) -> String {
+		let mut tera = tera();
+		tera.add_raw_template("test", template).unwrap();
+		let mut context = Context::new();
+		context.insert("value", &value);
+		tera.render("test", &context).map_err(diagnose).unwrap()
+	}
+
+	#[test]
+	fn humanize_number() {
+		assert_eq!(render("{{ value | humanize_number }}", json!(0)), "0");
+		assert_eq!(render("{{ value | humanize_number }}", json!(999)), "999");
+		assert_eq!(
+			render("{{ value | humanize_number }}", json!(1234567)),
+			"1,234,567"
+		);
+		assert_eq!(
+			render("{{ value | humanize_number }}", json!(-1234567)),
+			"-1,234,567"
+		);
+		assert_eq!(
+			render("{{ value | humanize_number }}", json!(u64::MAX)),
+			"18,446,744,073,709,551,615"
+		);
+		assert_eq!(
+			render("{{ value | humanize_number }}", json!(1234.5678)),
+			"1,235"
+		);
+		assert_eq!(
+			render(
+				"{{ value | humanize_number(decimals=2) }}",
+				json!(1234.5678)
+			),
+			"1,234.57"
+		);
+		assert_eq!(
+			render("{{ value | humanize_number(decimals=1) }}", json!(1000)),
+			"1,000.0"
+		);
+		assert_eq!(
+			render(
+				"{{ value | humanize_number(separator=' ') }}",
+				json!("98765.4")
+			),
+			"98 765"
+		);
+		assert_eq!(
+			render(
+				"{{ value | humanize_number(decimals=2) }}",
+				json!("-12345678901234567890.125")
+			),
+			"-12,345,678,901,234,567,890.13"
+		);
+		assert_eq!(
+			render("{{ value | humanize_number(decimals=1) }}", json!("999.95")),
+			"1,000.0"
+		);
+		assert_eq!(render("{{ value | humanize_number }}", json!("-0.4")), "0");
+		assert_eq!(render("{{ value | humanize_number }}", json!(null)), "");
+	}
+
+	#[test]
+	fn round_decimal() {
+		assert_eq!(super::round_decimal("1.5", 0).as_deref(), Some("2"));
+		assert_eq!(super::round_decimal("1.45", 1).as_deref(), Some("1.5"));
+		assert_eq!(super::round_decimal("-1.44", 1).as_deref(), Some("-1.4"));
+		assert_eq!(super::round_decimal("007", 2).as_deref(), Some("7.00"));
+		assert_eq!(super::round_decimal(".5", 0).as_deref(), Some("1"));
+		assert_eq!(super::round_decimal("99", 0).as_deref(), Some("99"));
+		assert_eq!(super::round_decimal("NaN", 0), None);
+		assert_eq!(super::round_decimal("1e5", 0), None);
+		assert_eq!(super::round_decimal("-", 0), None);
+	}
+
+	#[test]
+	fn builtin_truncate_is_kept() {
+		assert_eq!(
+			render("{{ value | truncate(length=5) }}", json!("hello world")),
+			"hello…"
+		);
+	}
+
+	#[test]
+	fn humanize_number_not_a_number() {
+		let mut tera = tera();
+		tera.add_raw_template("test", "{{ value | humanize_number }}")
+			.unwrap();
+		let mut context = Context::new();
+		context.insert("value", "lots");
+		assert!(tera.render("test", &context).is_err());
+	}
+
+	#[test]
+	fn format_date() {
+		let timestamp = json!("2024-06-01T12:34:56.789+00:00");
+		assert_eq!(
+			render("{{ value | format_date }}", timestamp.clone()),
+			"2024-06-01 12:34"
+		);
+		assert_eq!(
+			render(
+				"{{ value | format_date(format='%d %b %Y %H:%M %Z', timezone='Pacific/Auckland') }}",
+				timestamp
+			),
+			"02 Jun 2024 00:34 NZST"
+		);
+		assert_eq!(
+			render(
+				"{{ value | format_date }}",
+				json!("2024-06-01T12:34:56.789")
+			),
+			"2024-06-01 12:34"
+		);
+		assert_eq!(
+			render(
+				"{{ value | format_date }}",
+				json!("2024-06-01 12:34:56.789 UTC")
+			),
+			"2024-06-01 12:34"
+		);
+		assert_eq!(
+			render(
+				"{{ value | format_date(format='%A') }}",
+				json!("2024-06-01")
+			),
+			"Saturday"
+		);
+		assert_eq!(
+			render("{{ value | format_date }}", json!(1717245296)),
+			"2024-06-01 12:34"
+		);
+		assert_eq!(render("{{ value | format_date }}", json!(null)), "");
+	}
+
+	#[test]
+	fn format_date_errors() {
+		let mut tera = tera();
+		tera.add_raw_template("bad-date", "{{ value | format_date }}")
+			.unwrap();
+		tera.add_raw_template(
+			"bad-zone",
+			"{{ value | format_date(timezone='Mars/Olympus') }}",
+		)
+		.unwrap();
+		let mut context = Context::new();
+		context.insert("value", "yesterday");
+		assert!(tera.render("bad-date", &context).is_err());
+		context.insert("value", "2024-06-01");
+		assert!(tera.render("bad-zone", &context).is_err());
+	}
+
+	#[test]
+	fn truncate_chars() {
+		assert_eq!(
+			render(
+				"{{ value | truncate_chars(length=5) }}",
+				json!("hello world")
+			),
+			"hello…"
+		);
+		assert_eq!(
+			render(
+				"{{ value | truncate_chars(length=5, end='...') }}",
+				json!("héllo world")
+			),
+			"héllo..."
+		);
+		assert_eq!(
+			render(
+				"{{ value | truncate_chars(length=20) }}",
+				json!("hello world")
+			),
+			"hello world"
+		);
+		assert_eq!(
+			render("{{ value | truncate_chars(length=3) }}", json!(123456)),
+			"123…"
+		);
+		assert_eq!(render("{{ value | truncate_chars }}", json!(null)), "");
+	}
+
+	#[test]
+	fn unknown_helper() {
+		let mut tera = tera();
+		tera.add_raw_template("test", "{{ value | humanise_number }}")
+			.unwrap();
+		let mut context = Context::new();
+		context.insert("value", &1);
+		let err = tera.render("test", &context).map_err(diagnose).unwrap_err();
+		let err = err.chain().map(|err| err.to_string()).collect::<Vec<_>>();
+		assert_eq!(
+			err,
+			[
+				"Failed to render 'test'",
+				"unknown template helper \"humanise_number\""
+			]
+		);
+	}
+}
@@ -1,6 +1,6 @@
-use std::io::Write;
+use std::io::{IsTerminal as _, Write};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use miette::{Context as _, IntoDiagnostic, Result};
 
 use crate::actions::Context;
@@ -29,6 +29,26 @@ pub struct PsqlArgs {
 	#[arg(short = 'U', long, alias = "u")]
 	pub username: Option<String>,
 
+	/// Connect to a different database than Tamanu's.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `-d, --dbname NAME`"))]
+	#[arg(short, long, value_name = "NAME")]
+	pub dbname: Option<String>,
+
+	/// Connect to postgres on this host.
+	///
+	/// By default this is `$PGHOST` if that's set, or else the local server, via a Unix-domain
+	/// socket on Unix or TCP/IP on Windows.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--host HOST`"))]
+	#[arg(long)]
+	pub host: Option<String>,
+
+	/// Connect to postgres on this port.
+	///
+	/// By default this is `$PGPORT` if that's set, or else 5432.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `-p, --port PORT`"))]
+	#[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+	pub port: Option<u16>,
+
 	/// Enable write mode for this psql.
 	///
 	/// By default we set `TRANSACTION READ ONLY` for the session, which prevents writes. To enable
@@ -37,6 +57,87 @@ pub struct PsqlArgs {
 	#[cfg_attr(docsrs, doc("\n\n**Flag**: `-W, --write`"))]
 	#[arg(short = 'W', long)]
 	pub write: bool,
+
+	/// Keep a separate psql history for each database.
+	///
+	/// By default psql shares a single history file across all databases, so recalling history
+	/// (up-arrow, Ctrl-R) in one database surfaces commands typed in another. With this flag, the
+	/// history is kept in `~/.psql_history-DBNAME` instead.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--per-database-history`"))]
+	#[arg(long)]
+	pub per_database_history: bool,
+
+	/// Abort any statement that takes longer than this.
+	///
+	/// This sets `statement_timeout` for the session, so runaway queries are cancelled by the
+	/// server rather than holding the connection. It's a duration string, e.g. `30s` or `5m`. It
+	/// can be changed within the session with `SET statement_timeout`.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--statement-timeout DURATION`"))]
+	#[arg(long)]
+	pub statement_timeout: Option<humantime::Duration>,
+
+	/// Display NULL values as this string.
+	///
+	/// By default NULLs are blank, which makes them look the same as empty strings. Something like
+	/// `∅` or `(null)` tells them apart. It can be changed within the session with `\pset null`.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--null STRING`"))]
+	#[arg(long)]
+	pub null: Option<String>,
+
+	/// Run quietly, for scripting.
+	///
+	/// This hides the version banner and informational messages such as command tags, leaving only
+	/// query results and errors. It's the same as `psql --quiet`, and can be changed within the
+	/// session with `\set QUIET off`.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `-q, --quiet`"))]
+	#[arg(short, long)]
+	pub quiet: bool,
+
+	/// Fetch and display query results in batches of this many rows.
+	///
+	/// By default psql fetches the whole result of a query before displaying any of it, which can
+	/// use a lot of memory for large results. This sets psql's `FETCH_COUNT`, so rows are fetched
+	/// through a cursor and shown as they arrive. It can be changed within the session with
+	/// `\set FETCH_COUNT`.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--fetch-count ROWS`"))]
+	#[arg(long, value_name = "ROWS", value_parser = clap::value_parser!(u32).range(1..))]
+	pub fetch_count: Option<u32>,
+
+	/// Echo the SQL that's run, for an auditable transcript of scripts.
+	///
+	/// With `queries`, each query is printed before it's sent; `errors` only prints the queries that
+	/// failed; `all` also prints every input line as it's read, including meta-commands. This sets
+	/// psql's `ECHO`, and can be changed within the session with `\set ECHO`.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--echo none|errors|queries|all`"))]
+	#[arg(long, value_name = "MODE")]
+	pub echo: Option<Echo>,
+
+	/// Report how long each query takes.
+	///
+	/// This prints `Time: NN.NN ms` after each query, measured by psql from sending the query to
+	/// receiving its whole result, so it includes the round-trip to the server but not displaying
+	/// the result. It can be toggled within the session with `\timing`.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `--timing`"))]
+	#[arg(long)]
+	pub timing: bool,
+
+	/// Run this SQL and exit, instead of starting an interactive session.
+	///
+	/// Can be provided multiple times to run several commands in order. Execution stops at the
+	/// first error, and the command then exits with a non-zero code. Like with `psql`, SQL can also
+	/// be piped on stdin, which also stops at the first error.
+	#[cfg_attr(docsrs, doc("\n\n**Flag**: `-c, --command SQL`"))]
+	#[arg(short, long)]
+	pub command: Vec<String>,
+}
+
+/// What psql echoes, for `--echo`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Echo {
+	None,
+	Errors,
+	Queries,
+	All,
 }
 
 /// The Tamanu config only describing the part `psql` needs
@@ -77,26 +178,296 @@ pub async fn run(ctx: Context<TamanuArgs, PsqlArgs>) -> Result<()> {
 	}
 
 	let mut rc = tempfile::Builder::new().tempfile().into_diagnostic()?;
-	write!(
-		rc.as_file_mut(),
-		"{ro}",
-		ro = if ctx.args_sub.write {
-			""
-		} else {
-			"SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY;"
-		},
-	)
-	.into_diagnostic()?;
+	write!(rc.as_file_mut(), "{}", psqlrc(&ctx.args_sub)).into_diagnostic()?;
 
 	let psql_path = find_postgres_bin("psql")?;
 
-	// Use the default host, which is the localhost via Unix-domain socket on Unix or TCP/IP on Windows
-	duct::cmd!(psql_path, "--dbname", name, "--username", username)
-		.env("PSQLRC", rc.path())
-		.env("PGPASSWORD", password)
-		.run()
-		.into_diagnostic()
-		.wrap_err("failed to execute psql")?;
+	let interactive = std::io::stdin().is_terminal();
+	duct::cmd(
+		psql_path,
+		psql_args(name, username, interactive, &ctx.args_sub),
+	)
+	.env("PSQLRC", rc.path())
+	.env("PGPASSWORD", password)
+	.run()
+	.into_diagnostic()
+	.wrap_err("failed to execute psql")?;
 
 	Ok(())
 }
+
+/// The arguments passed to `psql`.
+///
+/// `interactive` is whether stdin is a terminal, rather than SQL piped in.
+///
+/// The database is `--dbname` or else the one from the Tamanu config. The host and port are only
+/// passed if given, so that psql otherwise uses `$PGHOST` and `$PGPORT`, or its defaults.
+fn psql_args(name: &str, username: &str, interactive: bool, args: &PsqlArgs) -> Vec<String> {
+	let mut psql = vec![
+		"--dbname".into(),
+		args.dbname.as_deref().unwrap_or(name).into(),
+		"--username".into(),
+		username.into(),
+	];
+
+	if let Some(host) = &args.host {
+		psql.extend(["--host".into(), host.clone()]);
+	}
+	if let Some(port) = args.port {
+		psql.extend(["--port".into(), port.to_string()]);
+	}
+
+	if args.quiet {
+		psql.push("--quiet".into());
+	}
+	if !interactive || !args.command.is_empty() {
+		// otherwise psql carries on after an error and exits with success
+		psql.extend(["--set".into(), "ON_ERROR_STOP=1".into()]);
+	}
+	for command in &args.command {
+		psql.extend(["--command".into(), command.clone()]);
+	}
+
+	psql
+}
+
+/// The contents of the PSQLRC file used for the session.
+///
+/// This runs quietly, so that the output of `--command` or piped SQL is only the query results.
+/// With `--quiet`, psql is quiet from the start and stays so after the file.
+fn psqlrc(args: &PsqlArgs) -> String {
+	let mut rc = String::new();
+
+	if args.per_database_history {
+		rc.push_str("\\set HISTFILE ~/.psql_history- :DBNAME\n");
+	}
+
+	if let Some(null) = &args.null {
+		// backslashes are escapes and quotes are doubled inside a quoted psql argument
+		let null = null.replace('\\', "\\\\").replace('\'', "''");
+		rc.push_str(&format!("\\pset null '{null}'\n"));
+	}
+
+	if args.timing {
+		rc.push_str("\\timing on\n");
+	}
+
+	if let Some(rows) = args.fetch_count {
+		rc.push_str(&format!("\\set FETCH_COUNT {rows}\n"));
+	}
+
+	if !args.write {
+		rc.push_str("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY;\n");
+	}
+
+	if let Some(timeout) = args.statement_timeout {
+		// zero would disable the timeout instead, so round up to the smallest unit postgres takes
+		let millis = timeout.as_millis().max(1);
+		rc.push_str(&format!("SET statement_timeout = {millis};\n"));
+	}
+
+	if !rc.is_empty() && !args.quiet {
+		rc = format!("\\set QUIET on\n{rc}\\set QUIET off\n");
+	}
+
+	// last, so that the lines above aren't echoed themselves
+	if let Some(echo) = args.echo.and_then(|echo| echo.to_possible_value()) {
+		rc.push_str(&format!("\\set ECHO {}\n", echo.get_name()));
+	}
+
+	rc
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(extra: &[&str]) -> PsqlArgs {
+		PsqlArgs::parse_from(["psql"].iter().chain(extra))
+	}
+
+	fn quiet(rc: &str) -> String {
+		format!("\\set QUIET on\n{rc}\\set QUIET off\n")
+	}
+
+	#[test]
+	fn psql_args_interactive() {
+		assert_eq!(
+			psql_args("tamanu", "tamanu", true, &args(&[])),
+			["--dbname", "tamanu", "--username", "tamanu"]
+		);
+	}
+
+	#[test]
+	fn psql_args_piped() {
+		assert_eq!(
+			psql_args("tamanu", "tamanu", false, &args(&[])),
+			[
+				"--dbname",
+				"tamanu",
+				"--username",
+				"tamanu",
+				"--set",
+				"ON_ERROR_STOP=1"
+			]
+		);
+	}
+
+	#[test]
+	fn psql_args_command() {
+		assert_eq!(
+			psql_args(
+				"tamanu",
+				"tamanu",
+				true,
+				&args(&["-c", "SELECT 1", "--command", "SELECT 2"])
+			),
+			[
+				"--dbname",
+				"tamanu",
+				"--username",
+				"tamanu",
+				"--set",
+				"ON_ERROR_STOP=1",
+				"--command",
+				"SELECT 1",
+				"--command",
+				"SELECT 2"
+			]
+		);
+	}
+
+	#[test]
+	fn psql_args_connection() {
+		assert_eq!(
+			psql_args(
+				"tamanu",
+				"tamanu",
+				true,
+				&args(&["-d", "other", "--host", "db.internal", "-p", "5433"])
+			),
+			[
+				"--dbname",
+				"other",
+				"--username",
+				"tamanu",
+				"--host",
+				"db.internal",
+				"--port",
+				"5433"
+			]
+		);
+		assert!(PsqlArgs::try_parse_from(["psql", "--port", "0"]).is_err());
+	}
+
+	#[test]
+	fn psql_args_quiet() {
+		assert_eq!(
+			psql_args("tamanu", "tamanu", true, &args(&["-q"])),
+			["--dbname", "tamanu", "--username", "tamanu", "--quiet"]
+		);
+	}
+
+	#[test]
+	fn psqlrc_default() {
+		assert_eq!(
+			psqlrc(&args(&[])),
+			quiet("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY;\n")
+		);
+	}
+
+	#[test]
+	fn psqlrc_quiet() {
+		assert_eq!(
+			psqlrc(&args(&["--null", "-", "--statement-timeout", "1s"])),
+			"\\set QUIET on\n\
+			\\pset null '-'\n\
+			SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY;\n\
+			SET statement_timeout = 1000;\n\
+			\\set QUIET off\n"
+		);
+	}
+
+	#[test]
+	fn psqlrc_quiet_flag() {
+		assert_eq!(
+			psqlrc(&args(&["--quiet", "--null", "-"])),
+			"\\pset null '-'\n\
+			SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY;\n"
+		);
+	}
+
+	#[test]
+	fn psqlrc_write() {
+		assert_eq!(psqlrc(&args(&["--write"])), "");
+	}
+
+	#[test]
+	fn psqlrc_statement_timeout() {
+		assert_eq!(
+			psqlrc(&args(&["--statement-timeout", "1m 30s", "--write"])),
+			quiet("SET statement_timeout = 90000;\n")
+		);
+		assert_eq!(
+			psqlrc(&args(&["--statement-timeout", "100us", "--write"])),
+			quiet("SET statement_timeout = 1;\n")
+		);
+		assert!(PsqlArgs::try_parse_from(["psql", "--statement-timeout", "soon"]).is_err());
+	}
+
+	#[test]
+	fn psqlrc_null() {
+		assert_eq!(
+			psqlrc(&args(&["--null", "∅", "--write"])),
+			quiet("\\pset null '∅'\n")
+		);
+		assert_eq!(
+			psqlrc(&args(&["--null", "it's null", "--write"])),
+			quiet("\\pset null 'it''s null'\n")
+		);
+		assert_eq!(
+			psqlrc(&args(&["--null", "\\N", "--write"])),
+			quiet("\\pset null '\\\\N'\n")
+		);
+	}
+
+	#[test]
+	fn psqlrc_fetch_count() {
+		assert_eq!(
+			psqlrc(&args(&["--fetch-count", "1000", "--write"])),
+			quiet("\\set FETCH_COUNT 1000\n")
+		);
+		assert!(PsqlArgs::try_parse_from(["psql", "--fetch-count", "0"]).is_err());
+	}
+
+	#[test]
+	fn psqlrc_echo() {
+		assert_eq!(
+			psqlrc(&args(&["--echo", "queries"])),
+			quiet("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY;\n")
+				+ "\\set ECHO queries\n"
+		);
+		assert_eq!(
+			psqlrc(&args(&["--echo", "all", "--write"])),
+			"\\set ECHO all\n"
+		);
+		assert!(PsqlArgs::try_parse_from(["psql", "--echo", "everything"]).is_err());
+	}
+
+	#[test]
+	fn psqlrc_timing() {
+		assert_eq!(
+			psqlrc(&args(&["--timing", "--write"])),
+			quiet("\\timing on\n")
+		);
+		assert_eq!(psqlrc(&args(&["--write"])), "");
+	}
+
+	#[test]
+	fn psqlrc_per_database_history() {
+		assert_eq!(
+			psqlrc(&args(&["--per-database-history", "--write"])),
+			quiet("\\set HISTFILE ~/.psql_history- :DBNAME\n")
+		);
+	}
+}
@@ -131,7 +131,7 @@ pub fn logging_preargs() -> Option<WorkerGuard> {
 
 pub fn logging_postargs(args: &Args) -> Result<WorkerGuard> {
 	let (log_writer, guard) = if let Some(file) = &args.log_file {
-		let is_dir = metadata(&file).map_or(false, |info| info.is_dir());
+		let is_dir = metadata(file).is_ok_and(|info| info.is_dir());
 		let (dir, filename) = if is_dir {
 			(
 				file.to_owned(),
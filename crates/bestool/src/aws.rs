@@ -65,6 +65,10 @@ impl AwsArgs {
 }
 
 /// Get AWS config from the environment, or credentials files, or ambient, etc.
+#[allow(
+	deprecated,
+	reason = "the behaviour version is pinned on purpose, newer ones change the HTTPS client stack"
+)]
 pub async fn init(args: &AwsArgs) -> SdkConfig {
 	let mut config = ConfigLoader::default()
 		.behavior_version(BehaviorVersion::v2024_03_28())
@@ -13,8 +13,8 @@ pub(crate) mod args;
 pub(crate) mod aws;
 pub mod file_chunker;
 
-#[cfg(feature = "tamanu-alerts")]
-pub(crate) mod postgres_to_value;
+#[cfg(feature = "postgres-to-value")]
+pub mod postgres_to_value;
 
 #[allow(dead_code)] // some subcommands don't use it, but it's easier to have it everywhere
 pub(crate) const APP_NAME: &str = concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"));
@@ -1,16 +1,48 @@
-// Copied from https://docs.rs/crate/serde_postgres/latest/source
+//! Conversion of Postgres rows and values to JSON.
+//!
+//! This is used to expose query results to templates and other JSON consumers in a consistent
+//! way. Use [`row_to_json`] and [`rows_to_json`] for whole rows, or [`JsonValue`] to decode a
+//! single column with [`Row::try_get`].
+//!
+//! The mapping is:
+//!
+//! - `NULL`: `null`
+//! - `bool`: boolean
+//! - `int2`, `int4`, `int8`, `oid`: number
+//! - `float4`, `float8`: number, or string for `NaN` and infinities
+//! - `numeric`: number, or string for `NaN` and infinities; integers within the range of `i64` or
+//!   `u64` are exact, but larger integers and fractional values go through `f64`, so may be rounded
+//! - `json`, `jsonb`: the JSON value itself
+//! - `timestamptz`: RFC 3339 string in UTC
+//! - `timestamp`, `date`, `time`: ISO 8601 string
+//! - `uuid`: hyphenated string
+//! - `bytea`: hex string in Postgres' escape format (`\x...`)
+//! - text types (`text`, `varchar`, `bpchar`, `name`, `citext`, ...) and enums: string
+//! - domains: as the underlying type
+//! - arrays: arrays, nested for multi-dimensional arrays
+//! - composites: objects keyed by field name
+//! - anonymous records (e.g. `SELECT row(1, 'foo')`): objects keyed `f1`, `f2`, etc, like
+//!   `row_to_json`, with fields of non-builtin types as `(unknown)`
+//!
+//! Other types are rendered as the string `(unknown)`.
+
+// `Raw` is copied from https://docs.rs/crate/serde_postgres/latest/source
 // which seems to be gone from github and used outdated dependencies.
 // Copyright to the original authors (1aim), MIT+Apache-2.0 licensed.
 
 use std::{collections::HashMap, error::Error, ops::Deref};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use fallible_iterator::FallibleIterator as _;
+use serde_json::{Map, Number, Value};
 use tokio_postgres::{
-	types::{FromSql, Type},
+	types::{Field, FromSql, Kind, Type},
 	Row,
 };
 use uuid::Uuid;
 
+type BoxError = Box<dyn Error + Send + Sync>;
+
 /// The raw bytes of a value, allowing "conversion" from any postgres type.
 ///
 /// This type intentionally cannot be converted from `NULL`, and attempting to
@@ -18,7 +50,7 @@ use uuid::Uuid;
 pub struct Raw<'a>(pub &'a [u8]);
 
 impl<'a> FromSql<'a> for Raw<'a> {
-	fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+	fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Self, BoxError> {
 		Ok(Raw(raw))
 	}
 
@@ -27,7 +59,7 @@ impl<'a> FromSql<'a> for Raw<'a> {
 	}
 }
 
-impl<'a> Deref for Raw<'a> {
+impl Deref for Raw<'_> {
 	type Target = [u8];
 
 	fn deref(&self) -> &Self::Target {
@@ -35,48 +67,577 @@ impl<'a> Deref for Raw<'a> {
 	}
 }
 
-pub fn col_to_value(
-	col: &tokio_postgres::Column,
-	row: &tokio_postgres::Row,
-	i: usize,
-) -> serde_json::Value {
-	use serde_json::Value;
-	use tokio_postgres::types::Type;
+/// The JSON representation of a postgres value, allowing conversion from any postgres type.
+///
+/// Unlike [`Raw`], this can be converted from `NULL`, which becomes [`Value::Null`].
+///
+/// See the [module documentation](self) for the type mapping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonValue(pub Value);
+
+impl<'a> FromSql<'a> for JsonValue {
+	fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, BoxError> {
+		value_from_sql(ty, raw).map(Self)
+	}
 
-	if let Ok(None) = row.try_get::<_, Option<crate::postgres_to_value::Raw<'_>>>(i) {
-		return Value::Null;
+	fn from_sql_null(_ty: &Type) -> Result<Self, BoxError> {
+		Ok(Self(Value::Null))
 	}
 
-	match col.type_() {
-		t if *t == Type::BOOL => Value::Bool(row.try_get(i).unwrap()),
-		t if *t == Type::INT2 => {
-			Value::Number(serde_json::Number::from(row.try_get::<_, i16>(i).unwrap()))
-		}
-		t if *t == Type::INT4 => {
-			Value::Number(serde_json::Number::from(row.try_get::<_, i32>(i).unwrap()))
+	fn accepts(_ty: &Type) -> bool {
+		true
+	}
+}
+
+impl From<JsonValue> for Value {
+	fn from(value: JsonValue) -> Self {
+		value.0
+	}
+}
+
+fn value_from_sql(ty: &Type, raw: &[u8]) -> Result<Value, BoxError> {
+	match ty.kind() {
+		Kind::Array(inner) => array_from_sql(inner, raw),
+		Kind::Domain(inner) => value_from_sql(inner, raw),
+		Kind::Composite(fields) => composite_from_sql(fields, raw),
+		Kind::Enum(_) => Ok(Value::String(std::str::from_utf8(raw)?.into())),
+		_ if *ty == Type::RECORD => record_from_sql(raw),
+		_ => simple_from_sql(ty, raw),
+	}
+}
+
+fn simple_from_sql(ty: &Type, raw: &[u8]) -> Result<Value, BoxError> {
+	Ok(match *ty {
+		Type::BOOL => Value::Bool(bool::from_sql(ty, raw)?),
+		Type::INT2 => i16::from_sql(ty, raw)?.into(),
+		Type::INT4 => i32::from_sql(ty, raw)?.into(),
+		Type::INT8 => i64::from_sql(ty, raw)?.into(),
+		Type::OID => u32::from_sql(ty, raw)?.into(),
+		Type::FLOAT4 => float_to_value(f32::from_sql(ty, raw)?.into()),
+		Type::FLOAT8 => float_to_value(f64::from_sql(ty, raw)?),
+		Type::NUMERIC => numeric_to_value(numeric_from_sql(raw)?),
+		Type::JSON => serde_json::from_slice(raw)?,
+		Type::JSONB => match raw.split_first() {
+			Some((1, json)) => serde_json::from_slice(json)?,
+			_ => return Err("unsupported JSONB encoding version".into()),
+		},
+		Type::TIMESTAMPTZ => Value::String(DateTime::<Utc>::from_sql(ty, raw)?.to_rfc3339()),
+		Type::TIMESTAMP => Value::String(
+			NaiveDateTime::from_sql(ty, raw)?
+				.format("%Y-%m-%dT%H:%M:%S%.f")
+				.to_string(),
+		),
+		Type::DATE => Value::String(NaiveDate::from_sql(ty, raw)?.to_string()),
+		Type::TIME => Value::String(NaiveTime::from_sql(ty, raw)?.to_string()),
+		Type::UUID => Value::String(Uuid::from_sql(ty, raw)?.to_string()),
+		Type::BYTEA => Value::String(raw.iter().fold(String::from("\\x"), |hex, byte| {
+			hex + &format!("{byte:02x}")
+		})),
+		ref ty if <&str as FromSql<'_>>::accepts(ty) => {
+			Value::String(<&str as FromSql<'_>>::from_sql(ty, raw)?.into())
 		}
-		t if *t == Type::INT8 => {
-			Value::Number(serde_json::Number::from(row.try_get::<_, i64>(i).unwrap()))
+		_ => Value::String("(unknown)".into()),
+	})
+}
+
+fn float_to_value(float: f64) -> Value {
+	Number::from_f64(float).map_or_else(
+		|| {
+			Value::String(
+				match float {
+					f64::INFINITY => "Infinity",
+					f64::NEG_INFINITY => "-Infinity",
+					_ => "NaN",
+				}
+				.into(),
+			)
+		},
+		Value::Number,
+	)
+}
+
+fn numeric_to_value(numeric: String) -> Value {
+	numeric
+		.parse::<Number>()
+		.map_or_else(|_| Value::String(numeric), Value::Number)
+}
+
+/// Decode a binary `numeric` to its decimal string representation.
+///
+/// The wire format is a header of four 16-bit values (number of digits, weight of the first digit,
+/// sign, display scale) followed by the digits, each a 16-bit value in base 10000.
+fn numeric_from_sql(raw: &[u8]) -> Result<String, BoxError> {
+	const NUMERIC_NEG: u16 = 0x4000;
+	const NUMERIC_NAN: u16 = 0xC000;
+	const NUMERIC_PINF: u16 = 0xD000;
+	const NUMERIC_NINF: u16 = 0xF000;
+
+	let read = |n: usize| -> Result<u16, BoxError> {
+		raw.get(n * 2..n * 2 + 2)
+			.map(|b| u16::from_be_bytes([b[0], b[1]]))
+			.ok_or_else(|| "numeric value is truncated".into())
+	};
+
+	let ndigits = read(0)? as usize;
+	let weight = read(1)? as i16 as isize;
+	let sign = read(2)?;
+	let dscale = read(3)? as usize;
+	let digits = (0..ndigits)
+		.map(|n| read(4 + n))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	match sign {
+		NUMERIC_NAN => return Ok("NaN".into()),
+		NUMERIC_PINF => return Ok("Infinity".into()),
+		NUMERIC_NINF => return Ok("-Infinity".into()),
+		_ => {}
+	}
+
+	let digit = |n: isize| {
+		usize::try_from(n)
+			.ok()
+			.and_then(|n| digits.get(n))
+			.copied()
+			.unwrap_or(0)
+	};
+
+	let mut out = String::new();
+	if sign == NUMERIC_NEG {
+		out.push('-');
+	}
+
+	if weight < 0 {
+		out.push('0');
+	} else {
+		out.push_str(&digit(0).to_string());
+		for n in 1..=weight {
+			out.push_str(&format!("{:04}", digit(n)));
 		}
-		// TODO: BYTEA
-		_ if row.try_get::<_, DateTime<Utc>>(i).is_ok() => {
-			Value::String(row.try_get::<_, DateTime<Utc>>(i).unwrap().to_rfc3339())
+	}
+
+	if dscale > 0 {
+		let mut fraction = String::new();
+		let mut n = weight + 1;
+		while fraction.len() < dscale {
+			fraction.push_str(&format!("{:04}", digit(n)));
+			n += 1;
 		}
-		_ if row.try_get::<_, Uuid>(i).is_ok() => {
-			Value::String(row.try_get::<_, Uuid>(i).unwrap().to_string())
+		fraction.truncate(dscale);
+		out.push('.');
+		out.push_str(&fraction);
+	}
+
+	Ok(out)
+}
+
+fn array_from_sql(inner: &Type, raw: &[u8]) -> Result<Value, BoxError> {
+	let array = postgres_protocol::types::array_from_sql(raw)?;
+	let dimensions: Vec<usize> = array
+		.dimensions()
+		.map(|dim| Ok(usize::try_from(dim.len).unwrap_or_default()))
+		.collect()?;
+	let mut values: Vec<Value> = array
+		.values()
+		.map(|value| match value {
+			None => Ok(Value::Null),
+			Some(raw) => value_from_sql(inner, raw),
+		})
+		.collect()?;
+
+	// values are in row-major order, so chunk from the innermost dimension outwards
+	for &len in dimensions.iter().skip(1).rev() {
+		values = values
+			.chunks(len.max(1))
+			.map(|chunk| Value::Array(chunk.to_vec()))
+			.collect();
+	}
+
+	Ok(Value::Array(values))
+}
+
+/// A field of a composite value: its type OID, and its raw value unless it's `NULL`.
+type RawField<'a> = (u32, Option<&'a [u8]>);
+
+/// Split a composite or record value into its fields.
+fn record_fields(mut raw: &[u8]) -> Result<Vec<RawField<'_>>, BoxError> {
+	fn take<'a>(raw: &mut &'a [u8], n: usize) -> Result<&'a [u8], BoxError> {
+		if raw.len() < n {
+			return Err("composite value is truncated".into());
 		}
-		_ => Value::String(row.try_get(i).unwrap_or("(unknown)".into())),
+		let (head, tail) = raw.split_at(n);
+		*raw = tail;
+		Ok(head)
+	}
+
+	let count = i32::from_be_bytes(take(&mut raw, 4)?.try_into()?);
+	let mut fields = Vec::with_capacity(usize::try_from(count).unwrap_or_default());
+	for _ in 0..count {
+		let oid = u32::from_be_bytes(take(&mut raw, 4)?.try_into()?);
+		let len = i32::from_be_bytes(take(&mut raw, 4)?.try_into()?);
+		let value = match usize::try_from(len) {
+			Err(_) => None,
+			Ok(len) => Some(take(&mut raw, len)?),
+		};
+		fields.push((oid, value));
+	}
+
+	Ok(fields)
+}
+
+fn composite_from_sql(fields: &[Field], raw: &[u8]) -> Result<Value, BoxError> {
+	let values = record_fields(raw)?;
+	if values.len() != fields.len() {
+		return Err("composite field count doesn't match its type".into());
 	}
+
+	let mut map = Map::with_capacity(fields.len());
+	for (field, (_oid, value)) in fields.iter().zip(values) {
+		let value = match value {
+			None => Value::Null,
+			Some(raw) => value_from_sql(field.type_(), raw)?,
+		};
+		map.insert(field.name().into(), value);
+	}
+
+	Ok(Value::Object(map))
+}
+
+/// Anonymous records don't have a type describing their fields, but each field carries its OID.
+fn record_from_sql(raw: &[u8]) -> Result<Value, BoxError> {
+	let values = record_fields(raw)?;
+
+	let mut map = Map::with_capacity(values.len());
+	for (i, (oid, value)) in values.into_iter().enumerate() {
+		let value = match (value, Type::from_oid(oid)) {
+			(None, _) => Value::Null,
+			(Some(raw), Some(ty)) => value_from_sql(&ty, raw)?,
+			(Some(_), None) => Value::String("(unknown)".into()),
+		};
+		map.insert(format!("f{}", i + 1), value);
+	}
+
+	Ok(Value::Object(map))
 }
 
-pub fn rows_to_value_map(rows: &[Row]) -> Vec<HashMap<String, serde_json::Value>> {
+/// Convert the column at index `i` of a row to JSON.
+///
+/// Values that fail to decode are rendered as the string `(unknown)`.
+pub fn col_to_value(row: &Row, i: usize) -> Value {
+	row.try_get::<_, JsonValue>(i)
+		.map_or_else(|_| Value::String("(unknown)".into()), Value::from)
+}
+
+/// Convert a row to a JSON object, keyed by column name.
+pub fn row_to_json(row: &Row) -> Value {
+	Value::Object(
+		row.columns()
+			.iter()
+			.enumerate()
+			.map(|(i, col)| (col.name().to_owned(), col_to_value(row, i)))
+			.collect(),
+	)
+}
+
+/// Convert rows to a JSON array of objects, keyed by column name.
+pub fn rows_to_json(rows: &[Row]) -> Value {
+	Value::Array(rows.iter().map(row_to_json).collect())
+}
+
+/// Convert rows to a list of maps of column name to JSON value.
+pub fn rows_to_value_map(rows: &[Row]) -> Vec<HashMap<String, Value>> {
 	rows.iter()
 		.map(|row| {
-			let mut map = HashMap::new();
-			for (i, col) in row.columns().iter().enumerate() {
-				map.insert(col.name().to_owned(), col_to_value(col, row, i));
-			}
-			map
+			row.columns()
+				.iter()
+				.enumerate()
+				.map(|(i, col)| (col.name().to_owned(), col_to_value(row, i)))
+				.collect()
 		})
 		.collect()
 }
+
+#[cfg(test)]
+mod tests {
+	use bytes::{BufMut, BytesMut};
+	use postgres_protocol::{types::ArrayDimension, IsNull};
+	use serde_json::json;
+	use tokio_postgres::types::ToSql;
+
+	use super::*;
+
+	fn encode(ty: &Type, value: &(dyn ToSql + Sync)) -> BytesMut {
+		let mut buf = BytesMut::new();
+		value.to_sql_checked(ty, &mut buf).unwrap();
+		buf
+	}
+
+	fn decode(ty: &Type, value: &(dyn ToSql + Sync)) -> Value {
+		JsonValue::from_sql(ty, &encode(ty, value)).unwrap().0
+	}
+
+	fn numeric(ndigits: u16, weight: i16, sign: u16, dscale: u16, digits: &[u16]) -> Value {
+		let mut buf = BytesMut::new();
+		buf.put_u16(ndigits);
+		buf.put_i16(weight);
+		buf.put_u16(sign);
+		buf.put_u16(dscale);
+		for digit in digits {
+			buf.put_u16(*digit);
+		}
+		JsonValue::from_sql(&Type::NUMERIC, &buf).unwrap().0
+	}
+
+	#[test]
+	fn null() {
+		for ty in [Type::INT4, Type::TEXT, Type::JSONB, Type::INT4_ARRAY] {
+			assert_eq!(JsonValue::from_sql_null(&ty).unwrap().0, Value::Null);
+		}
+	}
+
+	#[test]
+	fn scalars() {
+		assert_eq!(decode(&Type::BOOL, &true), json!(true));
+		assert_eq!(decode(&Type::INT2, &-12_i16), json!(-12));
+		assert_eq!(decode(&Type::INT4, &42_i32), json!(42));
+		assert_eq!(decode(&Type::INT8, &i64::MAX), json!(i64::MAX));
+		assert_eq!(decode(&Type::OID, &1234_u32), json!(1234));
+		assert_eq!(decode(&Type::FLOAT4, &1.5_f32), json!(1.5));
+		assert_eq!(decode(&Type::FLOAT8, &-0.25_f64), json!(-0.25));
+		assert_eq!(decode(&Type::FLOAT8, &f64::NAN), json!("NaN"));
+		assert_eq!(decode(&Type::FLOAT8, &f64::INFINITY), json!("Infinity"));
+	}
+
+	#[test]
+	fn text() {
+		assert_eq!(decode(&Type::TEXT, &"hello"), json!("hello"));
+		assert_eq!(decode(&Type::VARCHAR, &"world"), json!("world"));
+		assert_eq!(decode(&Type::BPCHAR, &"ab  "), json!("ab  "));
+		assert_eq!(decode(&Type::NAME, &"pg_class"), json!("pg_class"));
+		assert_eq!(
+			decode(&Type::BYTEA, &vec![0xde_u8, 0xad, 0x01]),
+			json!("\\xdead01")
+		);
+	}
+
+	#[test]
+	fn numerics() {
+		assert_eq!(numeric(0, 0, 0, 0, &[]), json!(0));
+		assert_eq!(numeric(1, 0, 0, 0, &[42]), json!(42));
+		assert_eq!(numeric(2, 1, 0, 0, &[1, 2345]), json!(12345));
+		assert_eq!(numeric(2, 0, 0x4000, 2, &[3, 2500]), json!(-3.25));
+		assert_eq!(numeric(1, -1, 0, 4, &[5]), json!(0.0005));
+		assert_eq!(numeric(1, -2, 0, 8, &[12]), json!(0.00000012));
+		assert_eq!(numeric(2, 0, 0, 3, &[1, 5000]), json!(1.5));
+		assert_eq!(numeric(1, 5, 0, 0, &[1]), json!(1e20));
+		assert_eq!(numeric(0, 0, 0xC000, 0, &[]), json!("NaN"));
+		assert_eq!(numeric(0, 0, 0xD000, 0, &[]), json!("Infinity"));
+		assert_eq!(numeric(0, 0, 0xF000, 0, &[]), json!("-Infinity"));
+	}
+
+	#[test]
+	fn numeric_strings() {
+		let raw = |digits: &[u16], weight: i16, dscale: u16| {
+			let mut buf = BytesMut::new();
+			buf.put_u16(digits.len() as _);
+			buf.put_i16(weight);
+			buf.put_u16(0);
+			buf.put_u16(dscale);
+			for digit in digits {
+				buf.put_u16(*digit);
+			}
+			numeric_from_sql(&buf).unwrap()
+		};
+
+		assert_eq!(raw(&[1, 0, 1], 2, 0), "100000001");
+		assert_eq!(raw(&[1, 5000], 0, 3), "1.500");
+		assert_eq!(raw(&[12], -2, 8), "0.00000012");
+		assert_eq!(raw(&[1], 2, 2), "100000000.00");
+	}
+
+	#[test]
+	fn json() {
+		let value = json!({ "a": [1, 2, { "b": null }], "c": "d" });
+		assert_eq!(
+			JsonValue::from_sql(&Type::JSON, value.to_string().as_bytes())
+				.unwrap()
+				.0,
+			value
+		);
+
+		let mut jsonb = vec![1];
+		jsonb.extend(value.to_string().as_bytes());
+		assert_eq!(JsonValue::from_sql(&Type::JSONB, &jsonb).unwrap().0, value);
+	}
+
+	#[test]
+	fn datetimes() {
+		let dt = DateTime::parse_from_rfc3339("2024-06-01T12:34:56.789+12:00")
+			.unwrap()
+			.to_utc();
+		assert_eq!(
+			decode(&Type::TIMESTAMPTZ, &dt),
+			json!("2024-06-01T00:34:56.789+00:00")
+		);
+		assert_eq!(
+			decode(&Type::TIMESTAMP, &dt.naive_utc()),
+			json!("2024-06-01T00:34:56.789")
+		);
+		assert_eq!(decode(&Type::DATE, &dt.date_naive()), json!("2024-06-01"));
+		assert_eq!(decode(&Type::TIME, &dt.time()), json!("00:34:56.789"));
+	}
+
+	#[test]
+	fn uuid() {
+		let id = Uuid::parse_str("a1a2a3a4-b1b2-c1c2-d1d2-d3d4d5d6d7d8").unwrap();
+		assert_eq!(
+			decode(&Type::UUID, &id),
+			json!("a1a2a3a4-b1b2-c1c2-d1d2-d3d4d5d6d7d8")
+		);
+	}
+
+	#[test]
+	fn arrays() {
+		assert_eq!(
+			decode(&Type::INT4_ARRAY, &vec![1_i32, 2, 3]),
+			json!([1, 2, 3])
+		);
+		assert_eq!(
+			decode(&Type::TEXT_ARRAY, &vec![Some("a"), None, Some("c")]),
+			json!(["a", null, "c"])
+		);
+		assert_eq!(decode(&Type::INT4_ARRAY, &Vec::<i32>::new()), json!([]));
+	}
+
+	#[test]
+	fn nested_arrays() {
+		let mut buf = BytesMut::new();
+		postgres_protocol::types::array_to_sql(
+			[
+				ArrayDimension {
+					len: 2,
+					lower_bound: 1,
+				},
+				ArrayDimension {
+					len: 3,
+					lower_bound: 1,
+				},
+			],
+			Type::INT8.oid(),
+			[Some(1_i64), Some(2), None, Some(4), Some(5), Some(6)],
+			|value, buf| match value {
+				Some(value) => {
+					postgres_protocol::types::int8_to_sql(value, buf);
+					Ok(IsNull::No)
+				}
+				None => Ok(IsNull::Yes),
+			},
+			&mut buf,
+		)
+		.unwrap();
+
+		assert_eq!(
+			JsonValue::from_sql(&Type::INT8_ARRAY, &buf).unwrap().0,
+			json!([[1, 2, null], [4, 5, 6]])
+		);
+	}
+
+	#[test]
+	fn composites() {
+		let ty = Type::new(
+			"pair".into(),
+			0,
+			Kind::Composite(vec![
+				Field::new("id".into(), Type::INT4),
+				Field::new("label".into(), Type::TEXT),
+				Field::new("missing".into(), Type::BOOL),
+			]),
+			"public".into(),
+		);
+
+		let mut buf = BytesMut::new();
+		buf.put_i32(3);
+		buf.put_u32(Type::INT4.oid());
+		buf.put_i32(4);
+		buf.put_i32(7);
+		buf.put_u32(Type::TEXT.oid());
+		buf.put_i32(3);
+		buf.put_slice(b"foo");
+		buf.put_u32(Type::BOOL.oid());
+		buf.put_i32(-1);
+
+		assert_eq!(
+			JsonValue::from_sql(&ty, &buf).unwrap().0,
+			json!({ "id": 7, "label": "foo", "missing": null })
+		);
+	}
+
+	#[test]
+	fn records() {
+		let mut buf = BytesMut::new();
+		buf.put_i32(4);
+		buf.put_u32(Type::INT4.oid());
+		buf.put_i32(4);
+		buf.put_i32(1);
+		buf.put_u32(Type::TEXT.oid());
+		buf.put_i32(3);
+		buf.put_slice(b"foo");
+		buf.put_u32(Type::BOOL.oid());
+		buf.put_i32(1);
+		buf.put_u8(1);
+		// a user-defined type, which can't be looked up without a connection
+		buf.put_u32(100_000);
+		buf.put_i32(2);
+		buf.put_slice(b"??");
+
+		assert_eq!(
+			decode_raw(&Type::RECORD, &buf),
+			json!({ "f1": 1, "f2": "foo", "f3": true, "f4": "(unknown)" })
+		);
+
+		let mut nested = BytesMut::new();
+		nested.put_i32(2);
+		nested.put_u32(Type::RECORD.oid());
+		nested.put_i32(buf.len() as _);
+		nested.put_slice(&buf);
+		nested.put_u32(Type::INT8.oid());
+		nested.put_i32(-1);
+		assert_eq!(
+			decode_raw(&Type::RECORD, &nested),
+			json!({
+				"f1": { "f1": 1, "f2": "foo", "f3": true, "f4": "(unknown)" },
+				"f2": null,
+			})
+		);
+
+		// truncated
+		assert!(JsonValue::from_sql(&Type::RECORD, &[0, 0, 0, 1]).is_err());
+	}
+
+	#[test]
+	fn domains_and_enums() {
+		let domain = Type::new(
+			"positive".into(),
+			0,
+			Kind::Domain(Type::INT4),
+			"public".into(),
+		);
+		assert_eq!(decode_raw(&domain, &encode(&Type::INT4, &5_i32)), json!(5));
+
+		let enumeration = Type::new(
+			"mood".into(),
+			0,
+			Kind::Enum(vec!["happy".into(), "sad".into()]),
+			"public".into(),
+		);
+		assert_eq!(decode_raw(&enumeration, b"happy"), json!("happy"));
+	}
+
+	#[test]
+	fn unknown() {
+		assert_eq!(decode_raw(&Type::POINT, &[0; 16]), json!("(unknown)"));
+	}
+
+	fn decode_raw(ty: &Type, raw: &[u8]) -> Value {
+		JsonValue::from_sql(ty, raw).unwrap().0
+	}
+}
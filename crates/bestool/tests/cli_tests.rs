@@ -16,6 +16,7 @@ fn cli_tests() {
 	// Add more `cases.skip()` here if any test use Postgres.
 	if handle_res.is_err() {
 		cases.skip("tests/cmd/alerts.toml");
+		cases.skip("tests/cmd/alerts-json.toml");
 	}
 
 	cases.run();
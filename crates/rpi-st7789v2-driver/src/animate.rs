@@ -0,0 +1,213 @@
+use std::{
+	ops::ControlFlow,
+	thread::sleep,
+	time::{Duration, Instant},
+};
+
+use tracing::{debug, instrument};
+
+use crate::Result;
+
+/// Statistics about a finished animation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnimationStats {
+	/// How many frames were rendered.
+	pub rendered: u64,
+
+	/// How many frames were skipped because rendering overran the frame budget.
+	pub dropped: u64,
+}
+
+impl crate::Driver {
+	/// Call a render function at a target frame rate, until it asks to stop.
+	///
+	/// The render function is given the driver and the frame number, and should draw the whole
+	/// frame to the display (e.g. with [`print()`](crate::Driver::print)). It returns
+	/// [`ControlFlow::Break`] to stop the animation, which makes it easy to plumb in a stop signal
+	/// like an `AtomicBool` set from a signal handler.
+	///
+	/// Frames are paced against a fixed schedule from the start of the animation rather than
+	/// relative to the previous frame, so timing doesn't drift. If a frame overruns its budget,
+	/// the frames whose slots have already passed are skipped rather than rendered late, and are
+	/// counted as dropped. The frame number always matches the slot in the schedule, so an
+	/// animation driven from it stays on time even when frames are dropped.
+	///
+	/// Frame rates above one frame per nanosecond are capped to that.
+	///
+	/// # Panics
+	///
+	/// If `fps` is zero.
+	#[instrument(level = "debug", skip(self, render))]
+	pub fn animate(
+		&mut self,
+		fps: u32,
+		mut render: impl FnMut(&mut Self, u64) -> Result<ControlFlow<()>>,
+	) -> Result<AnimationStats> {
+		pace(&SystemClock(Instant::now()), fps, |frame| {
+			render(self, frame)
+		})
+	}
+}
+
+/// Where [`pace()`] gets the time from, so that it can be tested without waiting.
+trait Clock {
+	/// Time since the start of the animation.
+	fn elapsed(&self) -> Duration;
+
+	fn sleep(&self, duration: Duration);
+}
+
+struct SystemClock(Instant);
+
+impl Clock for SystemClock {
+	fn elapsed(&self) -> Duration {
+		self.0.elapsed()
+	}
+
+	fn sleep(&self, duration: Duration) {
+		sleep(duration);
+	}
+}
+
+fn pace<E>(
+	clock: &impl Clock,
+	fps: u32,
+	mut render: impl FnMut(u64) -> std::result::Result<ControlFlow<()>, E>,
+) -> std::result::Result<AnimationStats, E> {
+	assert!(fps > 0, "fps must be greater than zero");
+	// a zero budget would divide by zero below
+	let budget = (Duration::from_secs(1) / fps).max(Duration::from_nanos(1));
+
+	let mut stats = AnimationStats::default();
+	let mut frame = 0_u64;
+	loop {
+		if render(frame)?.is_break() {
+			break;
+		}
+		stats.rendered += 1;
+
+		let elapsed = clock.elapsed();
+		let current = (elapsed.as_nanos() / budget.as_nanos()) as u64;
+		if current > frame {
+			let dropped = current - frame;
+			debug!(frame, dropped, "render overran the frame budget");
+			stats.dropped += dropped;
+		}
+
+		frame = current.max(frame) + 1;
+		let deadline = Duration::from_nanos((budget.as_nanos() as u64).saturating_mul(frame));
+		if let Some(wait) = deadline.checked_sub(elapsed) {
+			clock.sleep(wait);
+		}
+	}
+
+	debug!(?stats, "animation stopped");
+	Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::Cell;
+
+	use super::*;
+
+	/// A clock that only moves forward when it's slept on, or told to.
+	#[derive(Default)]
+	struct FakeClock(Cell<Duration>);
+
+	impl FakeClock {
+		fn advance(&self, duration: Duration) {
+			self.0.set(self.0.get() + duration);
+		}
+	}
+
+	impl Clock for FakeClock {
+		fn elapsed(&self) -> Duration {
+			self.0.get()
+		}
+
+		fn sleep(&self, duration: Duration) {
+			self.advance(duration);
+		}
+	}
+
+	#[test]
+	fn paces_to_the_target_rate() {
+		let clock = FakeClock::default();
+		let mut frames = Vec::new();
+		let stats = pace::<()>(&clock, 50, |frame| {
+			frames.push((frame, clock.elapsed()));
+			Ok(if clock.elapsed() >= Duration::from_millis(500) {
+				ControlFlow::Break(())
+			} else {
+				ControlFlow::Continue(())
+			})
+		})
+		.unwrap();
+
+		// 500ms at 50fps is 25 frames, each 20ms apart
+		assert_eq!(
+			stats,
+			AnimationStats {
+				rendered: 25,
+				dropped: 0
+			}
+		);
+		assert!(frames
+			.iter()
+			.all(|(frame, at)| *at == Duration::from_millis(20) * *frame as u32));
+	}
+
+	#[test]
+	fn counts_dropped_frames() {
+		let clock = FakeClock::default();
+		let mut frames = Vec::new();
+		let stats = pace::<()>(&clock, 100, |frame| {
+			frames.push(frame);
+			if frame == 0 {
+				// overrun the budget by a few frames
+				clock.advance(Duration::from_millis(35));
+			}
+			Ok(if frame < 5 {
+				ControlFlow::Continue(())
+			} else {
+				ControlFlow::Break(())
+			})
+		})
+		.unwrap();
+
+		// frame 0 ran until 35ms, so the slots at 10, 20 and 30ms were missed
+		assert_eq!(frames, [0, 4, 5]);
+		assert_eq!(
+			stats,
+			AnimationStats {
+				rendered: 2,
+				dropped: 3
+			}
+		);
+	}
+
+	#[test]
+	fn caps_the_frame_rate() {
+		let clock = FakeClock::default();
+		let stats = pace::<()>(&clock, u32::MAX, |frame| {
+			Ok(if frame < 10 {
+				ControlFlow::Continue(())
+			} else {
+				ControlFlow::Break(())
+			})
+		})
+		.unwrap();
+
+		assert_eq!(stats.rendered, 10);
+		assert_eq!(clock.elapsed(), Duration::from_nanos(10));
+	}
+
+	#[test]
+	fn stops_with_errors() {
+		assert_eq!(
+			pace(&FakeClock::default(), 10, |_| Err("oh no")),
+			Err("oh no")
+		);
+	}
+}
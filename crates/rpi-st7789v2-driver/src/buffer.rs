@@ -7,6 +7,13 @@ use crate::{
 
 impl crate::Driver {
 	/// Probe how many bytes we can send at once.
+	///
+	/// This finds the largest SPI transfer the driver accepts and uses it as the chunk size (see
+	/// [`DriverArgs::chunk_size`](crate::DriverArgs::chunk_size)) from then on.
+	///
+	/// A full frame is 134,400 bytes (280×240 pixels at 2 bytes each). With spidev's default buffer
+	/// size of 4096 bytes that's 33 transfers, each with its own syscall and chip select toggle; with
+	/// `spidev.bufsiz=131072` it's 2.
 	#[instrument(level = "trace", skip(self))]
 	pub fn probe_buffer_length(&mut self) -> Result<()> {
 		self.flush_buffer()?;
@@ -50,6 +57,7 @@ impl crate::Driver {
 		}
 
 		tracing::debug!(n, "probed max usable spi buffer length");
+		self.chunk_size = n;
 		self.buffer = Vec::with_capacity(n);
 		Ok(())
 	}
@@ -69,26 +77,37 @@ impl crate::Driver {
 			return Ok(());
 		}
 
-		let new = Vec::with_capacity(self.buffer.capacity());
+		let new = Vec::with_capacity(self.chunk_size);
 		let buf = std::mem::replace(&mut self.buffer, new);
 		self.write_data(&buf)?;
 		Ok(())
 	}
 
+	/// Write data to the display in as few SPI transfers as the chunk size allows.
+	///
+	/// The internal buffer is flushed first, and left empty.
+	#[instrument(level = "trace", skip(self, bytes))]
+	pub fn write_data_chunked(&mut self, bytes: &[u8]) -> Result<()> {
+		self.flush_buffer()?;
+		let chunk_size = self.chunk_size;
+		write_chunks(bytes, chunk_size, |chunk| self.write_data(chunk))?;
+		Ok(())
+	}
+
 	/// Write some data to the internal buffer.
 	///
 	/// If the buffer is full, it will be flushed to the display repeatedly until all the data has
 	/// been processed. The buffer is never increased in size.
 	#[instrument(level = "trace", skip(self, bytes))]
 	pub fn write_data_buffered(&mut self, bytes: &[u8]) -> Result<()> {
-		let remaining = self.buffer.capacity() - self.buffer.len();
+		let remaining = self.chunk_size - self.buffer.len();
 		if bytes.len() > remaining {
 			self.flush_buffer()?;
 		}
 
-		for chunk in bytes.chunks(self.buffer.capacity()) {
+		for chunk in bytes.chunks(self.chunk_size) {
 			self.buffer.extend_from_slice(chunk);
-			if self.buffer.len() == self.buffer.capacity() {
+			if self.buffer.len() == self.chunk_size {
 				self.flush_buffer()?;
 			}
 		}
@@ -96,3 +115,44 @@ impl crate::Driver {
 		Ok(())
 	}
 }
+
+/// Call `write` with consecutive chunks of at most `chunk_size` bytes, returning how many.
+fn write_chunks(
+	bytes: &[u8],
+	chunk_size: usize,
+	mut write: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<usize> {
+	let mut writes = 0;
+	for chunk in bytes.chunks(chunk_size.max(1)) {
+		write(chunk)?;
+		writes += 1;
+	}
+	Ok(writes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn chunks_are_as_large_as_allowed() {
+		let bytes = vec![0; 10_000];
+		let mut sizes = Vec::new();
+		let writes = write_chunks(&bytes, 4096, |chunk| {
+			sizes.push(chunk.len());
+			Ok(())
+		})
+		.unwrap();
+		assert_eq!(writes, 3);
+		assert_eq!(sizes, [4096, 4096, 1808]);
+	}
+
+	#[test]
+	fn full_frame_transfers() {
+		let bytes = crate::SimpleImage::new(280, 240).to_bytes();
+		assert_eq!(bytes.len(), 134_400);
+		let writes = |chunk_size| write_chunks(&bytes, chunk_size, |_| Ok(())).unwrap();
+		assert_eq!(writes(4096), 33);
+		assert_eq!(writes(131_072), 2);
+	}
+}
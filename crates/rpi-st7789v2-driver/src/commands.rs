@@ -4,7 +4,7 @@
 /// Descriptions are derived from usage and [the datasheet for the ST7789V2 chip][ST7789V2].
 ///
 /// [ST7789V2]: https://files.waveshare.com/upload/c/c9/ST7789V2.pdf
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Command {
 	/// No-op (NOP).
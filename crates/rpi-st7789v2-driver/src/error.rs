@@ -15,6 +15,12 @@ pub enum Error {
 	)]
 	Spi(#[from] rppal::spi::Error),
 
+	#[cfg_attr(
+		feature = "miette",
+		diagnostic(help("PWM error, check the channel is enabled in config.txt"))
+	)]
+	Pwm(#[from] rppal::pwm::Error),
+
 	#[cfg_attr(feature = "miette", diagnostic(help("local (non-SPI/GPIO) I/O error")))]
 	Io(#[from] std::io::Error),
 }
@@ -1,6 +1,6 @@
 use embedded_graphics::{
 	draw_target::DrawTarget,
-	geometry::{Dimensions, Point, Size},
+	geometry::{Dimensions, OriginDimensions, Size},
 	pixelcolor::{
 		raw::{RawData, RawU16},
 		Rgb565,
@@ -8,8 +8,7 @@ use embedded_graphics::{
 	primitives::Rectangle,
 	Pixel,
 };
-use itertools::Itertools;
-use tracing::instrument;
+use tracing::{instrument, trace};
 
 use super::{
 	commands::*,
@@ -37,18 +36,25 @@ impl crate::Driver {
 
 		// if this doesn't work, let's have another look at the c driver code
 		self.command(Command::ColumnAddressSet)?;
-		self.write_data(&(self.x_offset + start.0).to_be_bytes())?;
-		self.write_data(&(self.x_offset + end.0).to_be_bytes())?;
+		self.write_data(&address_range(start.0, end.0, self.x_offset))?;
 		self.command(Command::RowAddressSet)?;
-		self.write_data(&(self.y_offset + start.1).to_be_bytes())?;
-		self.write_data(&(self.y_offset + end.1).to_be_bytes())?;
+		self.write_data(&address_range(start.1, end.1, self.y_offset))?;
 
 		Ok(())
 	}
 
-	/// Write an image to the screen, buffered.
+	/// Write an image to the screen.
+	///
+	/// The image is sent in as few SPI transfers as the chunk size allows, see
+	/// [`DriverArgs::chunk_size`](crate::DriverArgs::chunk_size). Like all drawing, this does
+	/// nothing while the display is asleep.
 	#[instrument(level = "trace", skip(self, image))]
 	pub fn print(&mut self, origin: (u16, u16), image: &SimpleImage) -> Result<()> {
+		if !self.awake {
+			trace!("asleep, not drawing");
+			return Ok(());
+		}
+
 		self.set_window(
 			origin,
 			(
@@ -57,10 +63,40 @@ impl crate::Driver {
 			),
 		)?;
 
+		self.command(Command::MemoryWrite)?;
+		self.write_data_chunked(&image.to_bytes())?;
+		self.command(Command::Nop)?;
+		Ok(())
+	}
+
+	/// Write part of an image to the screen, buffered.
+	///
+	/// The `region` is in image coordinates: only that rectangle of the image is sent, to the
+	/// matching area of the screen given where the image is placed (`origin`). When only a small
+	/// part of an image changes, e.g. a clock digit, this is much faster than [`print()`].
+	///
+	/// [`print()`]: crate::Driver::print
+	#[instrument(level = "trace", skip(self, image))]
+	pub fn print_region(
+		&mut self,
+		origin: (u16, u16),
+		image: &SimpleImage,
+		region: Rectangle,
+	) -> Result<()> {
+		if !self.awake {
+			trace!("asleep, not drawing");
+			return Ok(());
+		}
+
+		let (start, end) = region_window(origin, image, &region, (self.width, self.height))?;
+		self.set_window(start, end)?;
+
 		self.command(Command::MemoryWrite)?;
 		self.clear_buffer();
-		for line in &image.data().chunks((image.width as usize) * 2) {
-			self.write_data_buffered(&line.collect::<Vec<u8>>())?;
+		let x = start.0 - origin.0;
+		let width = end.0 - start.0 + 1;
+		for y in (start.1 - origin.1)..=(end.1 - origin.1) {
+			self.write_data_buffered(&image.row_data(x, y, width).collect::<Vec<u8>>())?;
 		}
 		self.flush_buffer()?;
 		self.command(Command::Nop)?;
@@ -70,6 +106,11 @@ impl crate::Driver {
 	/// Write a pixel to the screen, unbuffered.
 	#[instrument(level = "trace", skip(self))]
 	pub fn pixel(&mut self, x: u16, y: u16, colour: Rgb565) -> Result<()> {
+		if !self.awake {
+			trace!("asleep, not drawing");
+			return Ok(());
+		}
+
 		if x >= self.width || y >= self.height {
 			return Err(Error::Io(std::io::Error::new(
 				std::io::ErrorKind::InvalidInput,
@@ -85,12 +126,57 @@ impl crate::Driver {
 	}
 }
 
-impl Dimensions for crate::Driver {
-	fn bounding_box(&self) -> Rectangle {
-		Rectangle::new(
-			Point::new(0, 0),
-			Size::new(self.width.into(), self.height.into()),
-		)
+/// Encode an address range for the column or row address set commands.
+///
+/// The `end` is inclusive, and the panel's `offset` is applied to both ends.
+fn address_range(start: u16, end: u16, offset: u16) -> [u8; 4] {
+	let [start_hi, start_lo] = (offset + start).to_be_bytes();
+	let [end_hi, end_lo] = (offset + end).to_be_bytes();
+	[start_hi, start_lo, end_hi, end_lo]
+}
+
+/// Compute the screen window (start and inclusive end) for a region of an image placed at origin.
+fn region_window(
+	origin: (u16, u16),
+	image: &SimpleImage,
+	region: &Rectangle,
+	screen: (u16, u16),
+) -> Result<((u16, u16), (u16, u16))> {
+	let invalid = |message| {
+		Error::Io(std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			message,
+		))
+	};
+
+	let Some(bottom_right) = region.bottom_right() else {
+		return Err(invalid("region is empty"));
+	};
+	let image_box = image.bounding_box();
+	if !image_box.contains(region.top_left) || !image_box.contains(bottom_right) {
+		return Err(invalid("region is outside the image"));
+	}
+
+	// both corners are within the image, so they fit in u16, but placing them at origin might not
+	let place = |origin: u16, offset: i32| origin.checked_add(offset as u16);
+	let (Some(start_x), Some(start_y), Some(end_x), Some(end_y)) = (
+		place(origin.0, region.top_left.x),
+		place(origin.1, region.top_left.y),
+		place(origin.0, bottom_right.x),
+		place(origin.1, bottom_right.y),
+	) else {
+		return Err(invalid("region exceeds screen size"));
+	};
+	if end_x >= screen.0 || end_y >= screen.1 {
+		return Err(invalid("region exceeds screen size"));
+	}
+
+	Ok(((start_x, start_y), (end_x, end_y)))
+}
+
+impl OriginDimensions for crate::Driver {
+	fn size(&self) -> Size {
+		Size::new(self.width.into(), self.height.into())
 	}
 }
 
@@ -177,3 +263,46 @@ impl DrawTarget for crate::Driver {
 		self.print((x, y), &image)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use embedded_graphics::geometry::Point;
+
+	use super::*;
+
+	fn rect(x: i32, y: i32, w: u32, h: u32) -> Rectangle {
+		Rectangle::new(Point::new(x, y), Size::new(w, h))
+	}
+
+	#[test]
+	fn address_range_with_offset() {
+		assert_eq!(address_range(0, 279, 20), [0, 20, 1, 43]);
+		assert_eq!(address_range(10, 19, 0), [0, 10, 0, 19]);
+	}
+
+	#[test]
+	fn region_window_placement() {
+		let image = SimpleImage::new(100, 50);
+		assert_eq!(
+			region_window((10, 20), &image, &rect(5, 6, 8, 16), (280, 240)).unwrap(),
+			((15, 26), (22, 41))
+		);
+		assert_eq!(
+			region_window((0, 0), &image, &image.bounding_box(), (280, 240)).unwrap(),
+			((0, 0), (99, 49))
+		);
+	}
+
+	#[test]
+	fn region_window_bounds() {
+		let image = SimpleImage::new(100, 50);
+		assert!(region_window((0, 0), &image, &rect(0, 0, 0, 0), (280, 240)).is_err());
+		assert!(region_window((0, 0), &image, &rect(90, 0, 20, 10), (280, 240)).is_err());
+		assert!(region_window((0, 0), &image, &rect(-1, 0, 10, 10), (280, 240)).is_err());
+		assert!(region_window((200, 0), &image, &rect(80, 0, 10, 10), (280, 240)).is_err());
+		assert!(region_window((0, 200), &image, &rect(0, 40, 10, 10), (280, 240)).is_err());
+		assert!(region_window((200, 0), &image, &rect(70, 0, 10, 10), (280, 240)).is_ok());
+		assert!(region_window((u16::MAX, 0), &image, &rect(1, 0, 10, 10), (280, 240)).is_err());
+		assert!(region_window((0, u16::MAX - 5), &image, &rect(0, 0, 10, 10), (280, 240)).is_err());
+	}
+}
@@ -1,4 +1,4 @@
-use bitvec::BitArr;
+use bitvec::{order::Msb0, BitArr};
 use tracing::{debug, instrument};
 
 /// Memory access control settings.
@@ -17,7 +17,7 @@ use tracing::{debug, instrument};
 /// let control = MemoryAccessControl::default().row_order(Vertical::TopToBottom);
 /// ```
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct MemoryAccessControl(BitArr!(for 8, in u8));
+pub struct MemoryAccessControl(BitArr!(for 8, in u8, Msb0));
 
 /// Vertical refresh order values.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -114,6 +114,60 @@ impl From<MemoryAccessControl> for u8 {
 	}
 }
 
+/// Display rotation.
+///
+/// This is clockwise from the default orientation, which is landscape (280×240). Rotating by 90 or
+/// 270 degrees makes the display portrait (240×280).
+///
+/// See [`DriverArgs::rotation`](crate::DriverArgs::rotation).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Rotation {
+	#[default]
+	#[cfg_attr(feature = "clap", value(name = "0"))]
+	Deg0,
+	#[cfg_attr(feature = "clap", value(name = "90"))]
+	Deg90,
+	#[cfg_attr(feature = "clap", value(name = "180"))]
+	Deg180,
+	#[cfg_attr(feature = "clap", value(name = "270"))]
+	Deg270,
+}
+
+impl Rotation {
+	/// The memory access control settings which implement this rotation.
+	pub fn memory_access_control(self) -> MemoryAccessControl {
+		let control = MemoryAccessControl::default();
+		match self {
+			Self::Deg0 => control,
+			Self::Deg90 => control.inverted().col_order(Horizontal::RightToLeft),
+			Self::Deg180 => control
+				.row_order(Vertical::BottomToTop)
+				.col_order(Horizontal::RightToLeft),
+			Self::Deg270 => control.inverted().row_order(Vertical::BottomToTop),
+		}
+	}
+
+	/// Width and height of the display in this rotation.
+	pub(crate) fn dimensions(self) -> (u16, u16) {
+		match self {
+			Self::Deg0 | Self::Deg180 => (280, 240),
+			Self::Deg90 | Self::Deg270 => (240, 280),
+		}
+	}
+
+	/// Column and row offsets of the visible area in this rotation.
+	///
+	/// The visible area is centered in the controller's 320-line memory, so the offset is the same
+	/// from either end, and only moves between columns and rows.
+	pub(crate) fn offsets(self) -> (u16, u16) {
+		match self {
+			Self::Deg0 | Self::Deg180 => (20, 0),
+			Self::Deg90 | Self::Deg270 => (0, 20),
+		}
+	}
+}
+
 /// Set RGB mode with 65K colours.
 ///
 /// See [`Command::InterfacePixelFormat`](crate::Command::InterfacePixelFormat).
@@ -216,19 +270,19 @@ pub fn frame_rate(rate: u8) -> u8 {
 		58 | 59 => 0x10,
 		60 | 61 => 0x0F,
 		62 | 63 => 0x0E,
-		64 | 65 | 66 => 0x0D,
+		64..=66 => 0x0D,
 		67 | 68 => 0x0C,
-		69 | 70 | 71 => 0x0B,
-		72 | 73 | 74 => 0x0A,
-		75 | 76 | 77 => 0x09,
-		78 | 79 | 80 | 81 => 0x08,
-		82 | 83 | 84 | 85 => 0x07,
-		86 | 87 | 88 | 89 => 0x06,
-		90 | 91 | 92 | 93 => 0x05,
-		94 | 95 | 96 | 97 | 98 => 0x04,
-		99 | 100 | 101 | 102 | 103 | 104 => 0x03,
-		105 | 106 | 107 | 108 | 109 | 110 => 0x02,
-		111 | 112 | 113 | 114 | 115 | 116 | 117 | 118 => 0x01,
+		69..=71 => 0x0B,
+		72..=74 => 0x0A,
+		75..=77 => 0x09,
+		78..=81 => 0x08,
+		82..=85 => 0x07,
+		86..=89 => 0x06,
+		90..=93 => 0x05,
+		94..=98 => 0x04,
+		99..=104 => 0x03,
+		105..=110 => 0x02,
+		111..=118 => 0x01,
 		_high => 0x00,
 	};
 	debug!(rate, "frame rate");
@@ -270,7 +324,7 @@ pub fn power_control2(avdd: u8, avcl: u8, vdds: u8) -> u8 {
 		25 | 26 => 0x3,
 		_ => unreachable!(),
 	};
-	let byte = 0 | avdd << 6 | avcl << 4 | vdds;
+	let byte = (avdd << 6) | avcl << 4 | vdds;
 	debug!(avdd, avcl, vdds, byte, "power control values");
 	byte
 }
@@ -322,7 +376,7 @@ pub enum GateScanDirection {
 
 impl From<GateFlags> for u8 {
 	fn from(flags: GateFlags) -> u8 {
-		0 | (match flags.mirror {
+		(match flags.mirror {
 			GateMirror::Local => 0,
 			GateMirror::Full => 1,
 		} << 4) | (match flags.interlace {
@@ -334,3 +388,56 @@ impl From<GateFlags> for u8 {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn memory_access_control_bits() {
+		let byte = |control: MemoryAccessControl| u8::from(control);
+		assert_eq!(byte(MemoryAccessControl::default()), 0);
+		assert_eq!(
+			byte(MemoryAccessControl::default().row_order(Vertical::BottomToTop)),
+			0x80
+		);
+		assert_eq!(
+			byte(MemoryAccessControl::default().col_order(Horizontal::RightToLeft)),
+			0x40
+		);
+		assert_eq!(byte(MemoryAccessControl::default().inverted()), 0x20);
+		assert_eq!(byte(MemoryAccessControl::default().bgr()), 0x08);
+	}
+
+	#[test]
+	fn rotation_madctl() {
+		let madctl = |rotation: Rotation| u8::from(rotation.memory_access_control());
+		assert_eq!(madctl(Rotation::Deg0), 0x00);
+		assert_eq!(madctl(Rotation::Deg90), 0x60);
+		assert_eq!(madctl(Rotation::Deg180), 0xC0);
+		assert_eq!(madctl(Rotation::Deg270), 0xA0);
+	}
+
+	#[test]
+	fn rotation_size() {
+		assert_eq!(Rotation::Deg0.dimensions(), (280, 240));
+		assert_eq!(Rotation::Deg90.dimensions(), (240, 280));
+		assert_eq!(Rotation::Deg180.dimensions(), (280, 240));
+		assert_eq!(Rotation::Deg270.dimensions(), (240, 280));
+
+		assert_eq!(Rotation::Deg0.offsets(), (20, 0));
+		assert_eq!(Rotation::Deg90.offsets(), (0, 20));
+		assert_eq!(Rotation::Deg180.offsets(), (20, 0));
+		assert_eq!(Rotation::Deg270.offsets(), (0, 20));
+	}
+
+	#[cfg(feature = "clap")]
+	#[test]
+	fn rotation_values() {
+		use clap::ValueEnum as _;
+
+		assert_eq!(Rotation::from_str("90", false), Ok(Rotation::Deg90));
+		assert_eq!(Rotation::from_str("270", false), Ok(Rotation::Deg270));
+		assert!(Rotation::from_str("45", false).is_err());
+	}
+}
@@ -2,27 +2,45 @@ use std::{thread::sleep, time::Duration};
 
 use rppal::{
 	gpio::{Gpio, Level, OutputPin},
+	pwm::{Channel, Polarity, Pwm},
 	spi::{Bus, Mode, SlaveSelect, Spi},
 };
-use tracing::{instrument, trace};
+use tracing::{instrument, trace, warn};
 
-use super::{commands::*, error::Result, helpers::*};
+use super::{
+	commands::*,
+	error::{Error, Result},
+	helpers::*,
+};
 
 /// Driver for the LCD display.
 #[derive(Debug)]
 pub struct Driver {
 	pub(crate) spi: Spi,
-	pub(crate) backlight: OutputPin,
+	pub(crate) backlight: Backlight,
+	pub(crate) backlight_level: u8,
 	pub(crate) dc: OutputPin,
 	pub(crate) reset: OutputPin,
+	pub(crate) rotation: Rotation,
 	pub(crate) width: u16,
 	pub(crate) height: u16,
 	pub(crate) x_offset: u16,
 	pub(crate) y_offset: u16,
+	pub(crate) chunk_size: usize,
 	pub(crate) buffer: Vec<u8>,
 	pub(crate) awake: bool,
 }
 
+/// How the backlight is driven: plain on/off GPIO, or hardware PWM for brightness.
+#[derive(Debug)]
+pub(crate) enum Backlight {
+	Pin(OutputPin),
+	Pwm(Pwm),
+}
+
+/// PWM frequency for the backlight, high enough to not flicker visibly.
+const BACKLIGHT_PWM_FREQUENCY: f64 = 1000.0;
+
 /// Arguments to create a new LCD driver.
 ///
 /// This is a struct to hold the arguments for the LCD driver: SPI port and frequency, GPIO pins.
@@ -42,6 +60,15 @@ pub struct DriverArgs {
 	/// Defaults to 18.
 	pub backlight: u8,
 
+	/// Hardware PWM channel for the backlight, to control its brightness.
+	///
+	/// The channel must be routed to the backlight pin, e.g. with `dtoverlay=pwm` in the Pi's
+	/// `config.txt` for channel 0 on GPIO 18. If the channel can't be opened, the driver falls
+	/// back to switching the backlight pin on and off.
+	///
+	/// Defaults to `None`, which only does on/off.
+	pub backlight_pwm: Option<u8>,
+
 	/// GPIO pin number for the display's reset pin.
 	///
 	/// Defaults to 27.
@@ -61,6 +88,29 @@ pub struct DriverArgs {
 	///
 	/// Defaults to 20 MHz.
 	pub frequency: u32,
+
+	/// Maximum number of bytes to send in a single SPI transfer.
+	///
+	/// Image data is sent in chunks of this size, so the larger it is the fewer transfers a frame
+	/// takes. It must not be more than the SPI driver's buffer size (`spidev.bufsiz`), or writes
+	/// will fail with "Message too long". Use [`Driver::probe_buffer_length()`] to find and set
+	/// the largest usable size at runtime instead.
+	///
+	/// Defaults to 4096, which is spidev's default buffer size.
+	pub chunk_size: usize,
+
+	/// Rotation of the display.
+	///
+	/// This swaps the width and height for 90 and 270 degrees, so the [`image()`] and
+	/// [`embedded_graphics`] bounds are in the rotated orientation. It's applied by [`init()`], and
+	/// restored by [`wake()`].
+	///
+	/// Defaults to no rotation (landscape).
+	///
+	/// [`image()`]: Driver::image
+	/// [`init()`]: Driver::init
+	/// [`wake()`]: Driver::wake
+	pub rotation: Rotation,
 }
 
 impl Default for DriverArgs {
@@ -68,10 +118,13 @@ impl Default for DriverArgs {
 		Self {
 			spi: 0,
 			backlight: 18,
+			backlight_pwm: None,
 			reset: 27,
 			dc: 25,
 			ce: 0,
 			frequency: 20_000_000,
+			chunk_size: 4096,
+			rotation: Rotation::default(),
 		}
 	}
 }
@@ -84,7 +137,20 @@ impl Driver {
 	#[instrument(level = "debug")]
 	pub fn new(args: DriverArgs) -> Result<Self> {
 		let gpio = Gpio::new()?;
-		let backlight = gpio.get(args.backlight)?.into_output();
+
+		// only claim the backlight pin as GPIO if not using PWM, or it would take it off the PWM
+		let pwm_channel = args.backlight_pwm.map(pwm_channel).transpose()?;
+		let backlight = match pwm_channel.map(open_pwm) {
+			Some(Ok(pwm)) => Backlight::Pwm(pwm),
+			Some(Err(err)) => {
+				warn!(
+					?err,
+					"cannot open PWM for the backlight, falling back to on/off"
+				);
+				Backlight::Pin(gpio.get(args.backlight)?.into_output())
+			}
+			None => Backlight::Pin(gpio.get(args.backlight)?.into_output()),
+		};
 		let dc = gpio.get(args.dc)?.into_output();
 		let reset = gpio.get(args.reset)?.into_output();
 
@@ -109,16 +175,21 @@ impl Driver {
 			Mode::Mode0,
 		)?;
 
+		let (width, height) = args.rotation.dimensions();
+		let (x_offset, y_offset) = args.rotation.offsets();
 		Ok(Self {
 			spi,
 			backlight,
+			backlight_level: 0,
 			dc,
 			reset,
-			width: 280,
-			height: 240,
-			x_offset: 20,
-			y_offset: 0,
-			buffer: Vec::with_capacity(4092),
+			rotation: args.rotation,
+			width,
+			height,
+			x_offset,
+			y_offset,
+			chunk_size: args.chunk_size.max(1),
+			buffer: Vec::with_capacity(args.chunk_size.max(1)),
 			awake: false,
 		})
 	}
@@ -145,10 +216,11 @@ impl Driver {
 		sleep(Duration::from_millis(20));
 		self.set_reset(Level::High);
 		sleep(Duration::from_millis(120)); // wait past cancel period
+		self.awake = false; // the reset puts the display to sleep
 
-		self.spi.write(&vec![0xaa; 3])?;
+		self.spi.write(&[0xaa; 3])?;
 		self.command(Command::MemoryAccessControl)?;
-		self.write_data(&[MemoryAccessControl::default().into()])?;
+		self.write_data(&[self.rotation.memory_access_control().into()])?;
 
 		self.command(Command::InterfacePixelFormat)?;
 		self.write_data(&[COLMOD_RGB_65K << 4 | COLMOD_16BPP])?;
@@ -175,7 +247,7 @@ impl Driver {
 		self.write_data(&[0x20])?;
 
 		self.command(Command::FrameRateControl)?;
-		self.write_data(&[0b000 << 5 | frame_rate(53)])?;
+		self.write_data(&[frame_rate(53)])?;
 
 		self.command(Command::PowerControl1)?;
 		self.write_data(&power_control1(68, 48, 23))?;
@@ -200,18 +272,49 @@ impl Driver {
 
 		self.command(Command::InversionOn)?;
 
-		self.backlight(true);
+		self.backlight(true)?;
 		self.wake()?;
-		self.command(Command::DisplayOn)?;
 
 		Ok(())
 	}
 
 	/// Turn the backlight on or off.
+	///
+	/// This is the same as setting the brightness to full or zero with [`set_backlight()`].
+	///
+	/// [`set_backlight()`]: Self::set_backlight
+	#[instrument(level = "trace", skip(self))]
+	pub fn backlight(&mut self, on: bool) -> Result<()> {
+		self.set_backlight(if on { u8::MAX } else { 0 })
+	}
+
+	/// Set the backlight brightness, from 0 (off) to 255 (full).
+	///
+	/// With a PWM channel configured (see [`DriverArgs::backlight_pwm`]) this sets the duty cycle;
+	/// otherwise the backlight is on at full brightness for any non-zero level.
+	///
+	/// This takes effect immediately and doesn't need the display to be initialised or awake, but
+	/// [`init()`](Self::init) turns the backlight on at full brightness, so set the level after.
 	#[instrument(level = "trace", skip(self))]
-	pub fn backlight(&mut self, on: bool) {
-		self.backlight
-			.write(if on { Level::High } else { Level::Low });
+	pub fn set_backlight(&mut self, level: u8) -> Result<()> {
+		self.apply_backlight(level)?;
+		self.backlight_level = level;
+		Ok(())
+	}
+
+	/// Drive the backlight at a level, without changing the configured level.
+	pub(crate) fn apply_backlight(&mut self, level: u8) -> Result<()> {
+		match &mut self.backlight {
+			Backlight::Pin(pin) => pin.write(if level > 0 { Level::High } else { Level::Low }),
+			Backlight::Pwm(pwm) => pwm.set_duty_cycle(duty_cycle(level))?,
+		}
+
+		Ok(())
+	}
+
+	/// The current backlight brightness, as last set.
+	pub fn backlight_level(&self) -> u8 {
+		self.backlight_level
 	}
 
 	/// Turn the display on or off.
@@ -242,28 +345,51 @@ impl Driver {
 		self.spi.write(bytes)?;
 		Ok(())
 	}
+}
 
-	/// Go to sleep.
-	#[instrument(level = "trace", skip(self))]
-	pub fn sleep(&mut self) -> Result<()> {
-		if self.awake {
-			self.command(Command::Sleep)?;
-			sleep(Duration::from_millis(5));
-			self.awake = false;
-		}
-
-		Ok(())
+fn pwm_channel(channel: u8) -> Result<Channel> {
+	match channel {
+		0 => Ok(Channel::Pwm0),
+		1 => Ok(Channel::Pwm1),
+		2 => Ok(Channel::Pwm2),
+		3 => Ok(Channel::Pwm3),
+		_ => Err(Error::Io(std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			format!("PWM channel must be 0 to 3, not {channel}"),
+		))),
 	}
+}
 
-	/// Wake up from sleep.
-	#[instrument(level = "trace", skip(self))]
-	pub fn wake(&mut self) -> Result<()> {
-		if !self.awake {
-			self.command(Command::WakeUp)?;
-			sleep(Duration::from_millis(120));
-			self.awake = true;
-		}
+fn open_pwm(channel: Channel) -> rppal::pwm::Result<Pwm> {
+	Pwm::with_frequency(
+		channel,
+		BACKLIGHT_PWM_FREQUENCY,
+		0.0,
+		Polarity::Normal,
+		true,
+	)
+}
 
-		Ok(())
+fn duty_cycle(level: u8) -> f64 {
+	f64::from(level) / f64::from(u8::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn duty_cycle_spans_the_full_range() {
+		assert_eq!(duty_cycle(0), 0.0);
+		assert_eq!(duty_cycle(u8::MAX), 1.0);
+		assert!((duty_cycle(128) - 0.5).abs() < 0.01);
+	}
+
+	#[test]
+	fn pwm_channel_out_of_range() {
+		assert_eq!(pwm_channel(3).unwrap(), Channel::Pwm3);
+		assert!(
+			matches!(pwm_channel(4), Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::InvalidInput)
+		);
 	}
 }
@@ -24,6 +24,9 @@
 //! # Ok(()) }
 //! ```
 
+#[doc(inline)]
+pub use animate::AnimationStats;
+
 #[doc(inline)]
 pub use commands::Command;
 
@@ -39,10 +42,12 @@ pub use io::*;
 #[doc(inline)]
 pub use simple::*;
 
+mod animate;
 mod buffer;
 mod commands;
 mod error;
 mod graphics;
 mod helpers;
 mod io;
+mod power;
 mod simple;
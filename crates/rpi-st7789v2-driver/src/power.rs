@@ -0,0 +1,124 @@
+use std::{thread::sleep, time::Duration};
+
+use tracing::instrument;
+
+use crate::{commands::Command, error::Result, helpers::Rotation};
+
+/// One step of a power mode change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+	Command(Command),
+	Data(u8),
+	Delay(Duration),
+}
+
+/// Turn the display off, then enter sleep.
+///
+/// The datasheet requires 5ms after SLPIN before any other command.
+fn sleep_sequence() -> [Step; 3] {
+	[
+		Step::Command(Command::DisplayOff),
+		Step::Command(Command::Sleep),
+		Step::Delay(Duration::from_millis(5)),
+	]
+}
+
+/// Leave sleep, restore the rotation, then turn the display on.
+///
+/// The datasheet requires 5ms after SLPOUT before any other command, and 120ms before SLPIN can be
+/// sent again; this waits the longer of the two so that a quick sleep/wake/sleep is safe.
+fn wake_sequence(rotation: Rotation) -> [Step; 5] {
+	[
+		Step::Command(Command::WakeUp),
+		Step::Delay(Duration::from_millis(120)),
+		Step::Command(Command::MemoryAccessControl),
+		Step::Data(rotation.memory_access_control().into()),
+		Step::Command(Command::DisplayOn),
+	]
+}
+
+impl crate::Driver {
+	/// Go to sleep.
+	///
+	/// This turns the display and the backlight off and puts the panel in its low-power sleep
+	/// mode, which keeps the contents of its memory. Drawing while asleep is a no-op: draw again
+	/// after [`wake()`](Self::wake) if the screen should change.
+	///
+	/// Does nothing if already asleep.
+	#[instrument(level = "trace", skip(self))]
+	pub fn sleep(&mut self) -> Result<()> {
+		if self.awake {
+			self.apply_backlight(0)?;
+			self.run_sequence(&sleep_sequence())?;
+			self.awake = false;
+		}
+
+		Ok(())
+	}
+
+	/// Wake up from sleep.
+	///
+	/// This leaves sleep mode without a full [`init()`](Self::init), turns the display back on,
+	/// and restores the configured rotation and backlight level.
+	///
+	/// Does nothing if already awake.
+	#[instrument(level = "trace", skip(self))]
+	pub fn wake(&mut self) -> Result<()> {
+		if !self.awake {
+			self.run_sequence(&wake_sequence(self.rotation))?;
+			self.apply_backlight(self.backlight_level)?;
+			self.awake = true;
+		}
+
+		Ok(())
+	}
+
+	/// Whether the display is awake, i.e. drawing will show.
+	pub fn is_awake(&self) -> bool {
+		self.awake
+	}
+
+	fn run_sequence(&mut self, steps: &[Step]) -> Result<()> {
+		for step in steps {
+			match *step {
+				Step::Command(command) => self.command(command)?,
+				Step::Data(byte) => self.write_data(&[byte])?,
+				Step::Delay(delay) => sleep(delay),
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sleep_turns_off_then_sleeps() {
+		assert_eq!(
+			sleep_sequence(),
+			[
+				Step::Command(Command::DisplayOff),
+				Step::Command(Command::Sleep),
+				Step::Delay(Duration::from_millis(5)),
+			]
+		);
+	}
+
+	#[test]
+	fn wake_waits_then_restores_rotation() {
+		assert_eq!(
+			wake_sequence(Rotation::Deg90),
+			[
+				Step::Command(Command::WakeUp),
+				Step::Delay(Duration::from_millis(120)),
+				Step::Command(Command::MemoryAccessControl),
+				Step::Data(0x60),
+				Step::Command(Command::DisplayOn),
+			]
+		);
+		assert_eq!(wake_sequence(Rotation::Deg0)[3], Step::Data(0x00));
+	}
+}
@@ -60,8 +60,23 @@ impl SimpleImage {
 		}
 	}
 
-	pub(crate) fn data(&self) -> impl Iterator<Item = u8> + '_ {
-		self.pixels.iter().flat_map(|p| p.to_be_bytes())
+	/// Pixel data for the whole image, ready to send to the display.
+	pub(crate) fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(self.pixels.len() * 2);
+		for pixel in &self.pixels {
+			bytes.extend_from_slice(&pixel.to_be_bytes());
+		}
+		bytes
+	}
+
+	/// Pixel data for `width` pixels of row `y`, starting at column `x`.
+	///
+	/// Panics if that's outside of the image.
+	pub(crate) fn row_data(&self, x: u16, y: u16, width: u16) -> impl Iterator<Item = u8> + '_ {
+		let start = (x as usize) + (y as usize) * (self.width as usize);
+		self.pixels[start..start + width as usize]
+			.iter()
+			.flat_map(|p| p.to_be_bytes())
 	}
 }
 
@@ -95,3 +110,22 @@ impl DrawTarget for SimpleImage {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn row_data_is_a_slice_of_the_row() {
+		let mut image = SimpleImage::new(4, 3);
+		for (n, pixel) in image.pixels.iter_mut().enumerate() {
+			*pixel = n as u16;
+		}
+
+		assert_eq!(
+			image.row_data(1, 2, 2).collect::<Vec<_>>(),
+			vec![0, 9, 0, 10]
+		);
+		assert_eq!(image.row_data(0, 0, 4).count(), 8);
+	}
+}